@@ -0,0 +1,34 @@
+#![windows_subsystem = "windows"]
+use alcro::{Asset, AssetProvider, Content, UIBuilder};
+use mime_guess::from_path;
+use rust_embed::RustEmbed;
+use std::sync::Arc;
+
+#[derive(RustEmbed)]
+#[folder = "examples/embed-assets"]
+struct Embedded;
+
+// The files are served to the browser straight from the binary, no HTTP
+// server is involved.
+struct Assets;
+
+impl AssetProvider for Assets {
+    fn get(&self, path: &str) -> Option<Asset> {
+        Embedded::get(path).map(|file| Asset {
+            data: file.data,
+            mime_type: from_path(path).first_or_octet_stream().to_string(),
+        })
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let ui = UIBuilder::new()
+        .content(Content::Assets(Arc::new(Assets)))
+        .size(400, 400)
+        .run()
+        .await?;
+
+    ui.wait_finish().await;
+    Ok(())
+}
@@ -1,21 +1,32 @@
 use std::{
     fmt::Display,
     sync::{
-        atomic::{AtomicBool, AtomicI32, Ordering},
+        atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering},
         Arc, Weak,
     },
 };
 
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use tokio::sync::{mpsc, oneshot, watch, Mutex};
+use tokio::sync::{broadcast, mpsc, oneshot, watch, Mutex};
 
 mod devtools;
-use devtools::{readloop, send, send_browser};
+use devtools::{readloop, send, send_browser, send_flat, send_to};
+mod fetch;
+use fetch::{assets_route, enable_fetch, intercepts, ASSETS_ORIGIN};
+pub use fetch::{
+    handler_route, intercept_key, intercept_route, remove_route, set_navigation_policy, set_route,
+    Asset, AssetProvider, FailReason, InterceptAction, NavigationAction, PolicyFunc, PolicyFuture,
+    Request, RequestHandler, Response, Route,
+};
+mod hot_reload;
 mod os;
+mod remember;
+pub use hot_reload::hot_reload;
 #[cfg(target_family = "windows")]
 use os::close_process_handle;
 use os::{kill_proc, new_process, wait_proc, PipeReader, PipeWriter, Process};
+pub use remember::{bounds_file, remember_bounds};
 
 /// A JS object. It is an alias for `serde_json::Value`. See it's documentation for how to use it.
 pub type JSObject = serde_json::Value;
@@ -46,6 +57,33 @@ impl From<JSObject> for JSError {
     }
 }
 
+/// An error from [`UI::call_js()`](crate::UI::call_js)
+#[derive(Debug, thiserror::Error)]
+pub enum CallJSError {
+    /// The arguments do not serialize to a JSON array
+    #[error("Arguments must serialize to an array: {0}")]
+    InvalidArguments(String),
+    /// The path does not name a function
+    #[error("{0} is not a function")]
+    NotAFunction(String),
+    /// The function threw or rejected with an `Error`
+    #[error("{name}: {message}")]
+    Exception {
+        /// Name of the error, e.g. `TypeError`
+        name: String,
+        /// The error message
+        message: String,
+        /// The JS stack trace, if available
+        stack: Option<String>,
+    },
+    /// The function threw or rejected with a value that is not an `Error`
+    #[error("Uncaught {0}")]
+    Thrown(JSObject),
+    /// The call could not be made, e.g. because the window is closed
+    #[error("Cannot call function: {0}")]
+    DevTools(#[from] JSError),
+}
+
 trait ToResultOfJSError {
     fn to_result_of_jserror(self) -> Result<(), JSError>;
 }
@@ -59,7 +97,128 @@ impl ToResultOfJSError for JSResult {
 }
 
 pub type BindingFuture = std::pin::Pin<Box<dyn std::future::Future<Output = JSResult> + Send>>;
-pub type BindingFunc = Arc<dyn Fn(Vec<JSObject>) -> BindingFuture + Sync + Send>;
+pub type BindingFunc = Arc<dyn Fn(CallInfo, Vec<JSObject>) -> BindingFuture + Sync + Send>;
+pub type CloseFuture = std::pin::Pin<Box<dyn std::future::Future<Output = bool> + Send>>;
+pub type CloseFunc = Arc<dyn Fn() -> CloseFuture + Sync + Send>;
+pub type NewWindowFuture = std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>>;
+pub type NewWindowFunc = Arc<dyn Fn(Arc<Window>) -> NewWindowFuture + Sync + Send>;
+
+/// Where a binding was called from.
+pub struct CallInfo {
+    pub window: Arc<Window>,
+    pub session: String,
+    pub context_id: i64,
+    pub origin: String,
+    pub url: String,
+}
+
+/// Which origins may use a binding.
+///
+/// An origin is written like `https://example.com` (with the port if it is not the
+/// default one). A host starting with `*.` matches all of its subdomains, e.g.
+/// `https://*.example.com`, and `*` matches every origin. Inline HTML has the origin
+/// `null`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Default)]
+pub enum OriginPolicy {
+    /// Any origin. This is the default.
+    #[default]
+    AllowAll,
+    /// Only the listed origins
+    AllowList(Vec<String>),
+}
+
+impl OriginPolicy {
+    /// Allow only the given origins
+    pub fn allow_list(origins: &[&str]) -> Self {
+        OriginPolicy::AllowList(origins.iter().map(|o| o.to_string()).collect())
+    }
+
+    /// Returns true if the policy allows `origin`
+    pub fn allows(&self, origin: &str) -> bool {
+        match self {
+            OriginPolicy::AllowAll => true,
+            OriginPolicy::AllowList(list) => list.iter().any(|p| origin_matches(p, origin)),
+        }
+    }
+
+    fn to_json(&self) -> JSObject {
+        match self {
+            OriginPolicy::AllowAll => JSObject::Null,
+            OriginPolicy::AllowList(list) => json!(list),
+        }
+    }
+}
+
+// Mirrored by the origin check in binding_script
+fn origin_matches(pattern: &str, origin: &str) -> bool {
+    if pattern == "*" || pattern == origin {
+        return true;
+    }
+    match pattern.split_once("://*.") {
+        Some((scheme, host)) => origin
+            .strip_prefix(scheme)
+            .and_then(|o| o.strip_prefix("://"))
+            .and_then(|o| o.strip_suffix(host))
+            .is_some_and(|sub| sub.ends_with('.') && sub.len() > 1),
+        None => false,
+    }
+}
+
+/// Options for a binding added with `UI::bind_with_options()`.
+#[derive(Debug, Clone, Default)]
+pub struct BindingOptions {
+    origin_policy: OriginPolicy,
+    max_concurrency: Option<usize>,
+    max_queue: Option<usize>,
+}
+
+impl BindingOptions {
+    /// Default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only expose the binding to frames whose origin the policy allows. Calls from
+    /// origins the window's own policy does not allow are rejected as well.
+    pub fn origin_policy(&mut self, policy: OriginPolicy) -> &mut Self {
+        self.origin_policy = policy;
+        self
+    }
+
+    /// Run at most `n` calls of the binding at the same time. Further calls wait in a
+    /// queue and start in the order they were made. By default there is no limit.
+    pub fn max_concurrency(&mut self, n: usize) -> &mut Self {
+        self.max_concurrency = Some(n.max(1));
+        self
+    }
+
+    /// Run the calls of the binding one at a time, strictly in the order they were
+    /// made. This is the same as `max_concurrency(1)`.
+    pub fn serial(&mut self) -> &mut Self {
+        self.max_concurrency(1)
+    }
+
+    /// Reject calls with a JS error when `n` calls are already waiting for their turn.
+    /// Only calls beyond the concurrency limit wait, so this has no effect without
+    /// one. By default the queue is unbounded.
+    pub fn max_queue(&mut self, n: usize) -> &mut Self {
+        self.max_queue = Some(n);
+        self
+    }
+}
+
+/// A binding call that was rejected because the calling frame's origin is not allowed.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct BlockedCall {
+    /// Name of the binding
+    pub name: String,
+    /// Origin of the calling frame
+    pub origin: String,
+    /// Url of the calling frame
+    pub url: String,
+}
 
 /// Where to log the browser's console messages and uncaught exceptions.
 ///
@@ -80,14 +239,311 @@ pub enum LogSink {
     File(std::sync::Mutex<std::fs::File>),
 }
 
+impl LogSink {
+    /// Open the sink writing to `output`, creating the log file
+    pub fn open(output: &LogOutput) -> std::io::Result<LogSink> {
+        Ok(match output {
+            LogOutput::Stdout => LogSink::Stdout,
+            LogOutput::Stderr => LogSink::Stderr,
+            LogOutput::File(path) => {
+                LogSink::File(std::sync::Mutex::new(std::fs::File::create(path)?))
+            }
+        })
+    }
+
+    fn write(&self, msg: &JSObject) {
+        use std::io::Write;
+        match self {
+            LogSink::Stdout => println!("Message: {}", msg),
+            LogSink::Stderr => eprintln!("Message: {}", msg),
+            LogSink::File(f) => {
+                let mut f = f.lock().expect("Unable to lock");
+                let _ = writeln!(f, "Message: {}", msg);
+            }
+        }
+    }
+}
+
+/// Settings a window starts with, applied before its content loads.
+#[derive(Clone, Default)]
+pub struct WindowConfig {
+    pub origin_policy: OriginPolicy,
+    pub routes: Vec<Route>,
+    pub navigation_policy: Option<PolicyFunc>,
+    /// Receives the windows the page opens
+    pub new_window_handler: Option<NewWindowFunc>,
+    /// Where to log instead of the browser's log sink
+    pub log_sink: Option<Arc<LogSink>>,
+    /// How the initial content is loaded
+    pub load_options: LoadOptions,
+}
+
+/// What to load in a window.
+pub enum Source {
+    Url(String),
+    /// Files served at [`ASSETS_ORIGIN`] by Fetch interception
+    Assets(Arc<dyn AssetProvider>),
+    /// HTML text written into the document loaded from `base_url`, which
+    /// relative urls in the text resolve against
+    Html {
+        html: String,
+        base_url: String,
+    },
+}
+
 /// Page load progress signals forwarded by the read loop.
 pub enum LoadEvent {
     /// Page.frameNavigated of the main frame, with its loaderId. Used to tell
     /// a navigation's own load event apart from stale ones (e.g. the initial
     /// about:blank page finishing to load after Page.navigate was sent).
     Navigated(String),
-    /// Page.loadEventFired
+    /// Page.frameNavigated of the main frame for a page restored from the
+    /// back/forward cache, with its loaderId. The page has loaded long before,
+    /// so no lifecycle events follow.
+    Restored(String),
+    /// Page.lifecycleEvent of the main frame, with its loaderId and name
+    Lifecycle(String, String),
+    /// Network.responseReceived for the document of the main frame, with its
+    /// loaderId and HTTP status
+    Response(String, u16),
+    /// Page.navigatedWithinDocument of the main frame. Ends waiting for the
+    /// next navigation, which did not load a new document.
+    WithinDocument,
+}
+
+/// What loading content waits for before it is done.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum WaitUntil {
+    /// The DOMContentLoaded event: the document is parsed, but images, stylesheets
+    /// etc. may still be loading
+    DomContentLoaded,
+    /// The load event: the document and its resources are loaded
+    Load,
+    /// No network connections for 500ms after the load event. Pages that keep a
+    /// connection open (e.g. long polling) never reach it.
+    NetworkIdle,
+    /// A JS expression, evaluated repeatedly from the moment the new document
+    /// exists until it is truthy, e.g. `window.app && app.ready`
+    Predicate(String),
+}
+
+/// Options for loading content, see [`UI::load_with_options()`](crate::UI::load_with_options).
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct LoadOptions {
+    wait_until: WaitUntil,
+    timeout: std::time::Duration,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoadOptions {
+    /// Wait for the load event, for at most 30 seconds
+    pub fn new() -> Self {
+        LoadOptions {
+            wait_until: WaitUntil::Load,
+            timeout: std::time::Duration::from_secs(30),
+        }
+    }
+
+    /// Set what to wait for
+    pub fn wait_until(&mut self, wait_until: WaitUntil) -> &mut Self {
+        self.wait_until = wait_until;
+        self
+    }
+
+    /// Set how long to wait before failing with [`LoadError::Timeout`]
+    pub fn timeout(&mut self, timeout: std::time::Duration) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Options for opening a window, see
+/// [`UI::new_window_with_options()`](crate::UI::new_window_with_options).
+#[derive(Debug, Clone, Default)]
+pub struct WindowOptions {
+    size: Option<(i32, i32)>,
+    position: Option<(i32, i32)>,
+    state: Option<WindowState>,
+    tab: bool,
+    log_output: Option<LogOutput>,
+}
+
+impl WindowOptions {
+    /// Open a new window with the browser's default size and position
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the outer width and height of the window
+    pub fn size(&mut self, width: i32, height: i32) -> &mut Self {
+        self.size = Some((width, height));
+        self
+    }
+
+    /// Set the screen position of the window's top left corner
+    pub fn position(&mut self, left: i32, top: i32) -> &mut Self {
+        self.position = Some((left, top));
+        self
+    }
+
+    /// Open the window maximized, minimized, fullscreen or normal. By default it
+    /// keeps the state the browser opens it in.
+    pub fn state(&mut self, state: WindowState) -> &mut Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Open a tab in an existing browser window instead of a new window. The size,
+    /// position and state do not apply to tabs.
+    pub fn tab(&mut self, tab: bool) -> &mut Self {
+        self.tab = tab;
+        self
+    }
+
+    /// Log the console messages and uncaught exceptions of this window (and the
+    /// windows it opens) to the given destination. By default it logs where the
+    /// window it was opened from does, or else to the browser's log output.
+    pub fn log_output(&mut self, log_output: LogOutput) -> &mut Self {
+        self.log_output = Some(log_output);
+        self
+    }
+
+    /// The Target.createTarget parameters opening the window
+    fn target_params(&self, headless: bool) -> JSObject {
+        let mut params = json!({ "url": "about:blank" });
+        if self.tab {
+            return params;
+        }
+        if !headless {
+            params["newWindow"] = json!(true);
+        }
+        if let Some((width, height)) = self.size {
+            params["width"] = json!(width);
+            params["height"] = json!(height);
+        }
+        if let Some((left, top)) = self.position {
+            params["left"] = json!(left);
+            params["top"] = json!(top);
+        }
+        if let Some(state) = self.state {
+            params["windowState"] = json!(state);
+        }
+        params
+    }
+}
+
+/// The log sink of a window opened with `options`, if it has its own.
+pub fn window_log_sink(options: &WindowOptions) -> std::io::Result<Option<Arc<LogSink>>> {
+    options
+        .log_output
+        .as_ref()
+        .map(|output| LogSink::open(output).map(Arc::new))
+        .transpose()
+}
+
+/// An error in loading content
+#[derive(Debug, thiserror::Error)]
+pub enum LoadError {
+    /// The content did not reach the awaited state in time
+    #[error("Loading timed out after {0:?}")]
+    Timeout(std::time::Duration),
+    /// The navigation failed, or the server answered with an HTTP error status
+    #[error("{0}")]
+    Navigation(NavigationError),
+    /// The content could not be loaded, e.g. because the window is closed
+    #[error("Cannot load content: {0}")]
+    DevTools(#[from] JSError),
+}
+
+/// A failed navigation, see [`LoadError::Navigation`].
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct NavigationError {
+    /// The url that was loaded
+    pub url: String,
+    /// The network error if the page could not be fetched, e.g.
+    /// `net::ERR_CONNECTION_REFUSED` or `net::ERR_NAME_NOT_RESOLVED`
+    pub net_error: Option<String>,
+    /// The HTTP status of the response, if the server answered
+    pub status: Option<u16>,
+}
+
+impl Display for NavigationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.net_error, self.status) {
+            (Some(e), _) => write!(f, "Cannot load {}: {}", self.url, e),
+            (None, Some(status)) => write!(f, "Cannot load {}: HTTP status {}", self.url, status),
+            (None, None) => write!(f, "Cannot load {}", self.url),
+        }
+    }
+}
+
+impl std::error::Error for NavigationError {}
+
+/// Fail with the HTTP status of a loaded document if it is an error status.
+fn check_status(url: &str, status: Option<u16>) -> Result<(), LoadError> {
+    match status {
+        Some(status) if status >= 400 => Err(LoadError::Navigation(NavigationError {
+            url: url.to_string(),
+            net_error: None,
+            status: Some(status),
+        })),
+        _ => Ok(()),
+    }
+}
+
+/// Progress of the navigations of a window's main frame, see
+/// [`UI::navigation_events()`](crate::UI::navigation_events).
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize)]
+pub enum NavigationEvent {
+    /// The main frame started loading
+    Started,
+    /// A new document replaced the previous one
+    Committed {
+        /// The url of the new document
+        url: String,
+    },
+    /// The DOMContentLoaded event of the document fired
+    DomContentLoaded,
+    /// The load event of the document fired. A page restored from the back/forward
+    /// cache reports it right after it is committed.
     Loaded,
+    /// The main frame stopped loading, successfully or not
+    Finished,
+    /// The url changed without a new document, through the history API or a
+    /// fragment navigation
+    WithinDocument {
+        /// The new url
+        url: String,
+    },
+}
+
+/// A change of a window, see [`UI::window_events()`](crate::UI::window_events).
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize)]
+pub enum WindowEvent {
+    /// The window was moved or resized
+    BoundsChanged(Bounds),
+    /// The window was maximized, minimized, made fullscreen or restored
+    StateChanged(WindowState),
+    /// The window got the input focus
+    Focused,
+    /// The window lost the input focus
+    Blurred,
+    /// The page became visible or hidden, e.g. because the window was minimized
+    VisibilityChanged {
+        /// Whether the page is visible now
+        visible: bool,
+    },
+}
+
+/// The last window geometry and visibility reported by the observer script
+struct ObservedWindow {
+    bounds: Bounds,
+    visible: bool,
 }
 
 /// The browser process, shared by all of its windows.
@@ -98,29 +554,165 @@ pub struct Chrome {
     #[cfg(target_family = "windows")]
     pid: usize,
     psend: Mutex<PipeWriter>,
-    // Pending session commands, tagged with their session id so they can be
+    // Pending session commands, tagged with their sessions so they can be
     // failed when that window closes instead of hanging forever.
-    pending: dashmap::DashMap<i32, (String, oneshot::Sender<JSResult>)>,
+    pending: dashmap::DashMap<i32, Pending>,
     pending_browser: dashmap::DashMap<i32, oneshot::Sender<JSResult>>,
     windows: dashmap::DashMap<String, Weak<Window>>,
     // Windows past createTarget but not yet in `windows`; the read loop must
     // not kill the browser while one is in flight.
     windows_in_creation: AtomicI32,
+    // Whether new pages are auto-attached, see update_auto_attach
+    auto_attach: Mutex<bool>,
+    // Number of Browser handles; while there are any the browser runs on
+    // without windows.
+    keep_alive: AtomicUsize,
     headless: bool,
     log_sink: Option<LogSink>,
     closed: AtomicBool,
+    // Set once the read loop ended, i.e. the browser exited
+    exited: watch::Sender<bool>,
     _tmpdir: Option<tempfile::TempDir>,
 }
 
+/// A command sent to a window or one of its child sessions, awaiting its
+/// response.
+pub struct Pending {
+    /// Session of the window the command belongs to
+    window: String,
+    /// Session the command is addressed to. Relay acknowledgements from the
+    /// sessions in between carry the same id and must not resolve it.
+    session: String,
+    reschan: oneshot::Sender<JSResult>,
+}
+
+/// A target auto-attached below a window, such as an out-of-process iframe.
+/// Its session is not reachable from the browser directly, only by relaying
+/// messages through `parent`.
+pub struct ChildSession {
+    parent: String,
+}
+
+/// A JS execution context reported by Runtime.executionContextCreated.
+pub struct ExecutionContext {
+    frame_id: String,
+    origin: String,
+    // The world name for isolated worlds, empty for the page's own context
+    name: String,
+    is_default: bool,
+}
+
+/// A stylesheet loaded by a document, reported by CSS.styleSheetAdded.
+pub struct StyleSheet {
+    frame_id: String,
+    source_url: String,
+}
+
+/// A rust function exposed to JS.
+pub struct Binding {
+    func: BindingFunc,
+    options: BindingOptions,
+    queue: Option<BindingQueue>,
+}
+
+/// A call of a binding, including sending its result back to JS.
+pub type BindingCall = std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>>;
+
+/// Calls of a binding with a concurrency limit. They are started in order by
+/// a worker task that ends when the binding is dropped.
+#[derive(Clone)]
+pub struct BindingQueue {
+    send: mpsc::UnboundedSender<BindingCall>,
+    max_concurrency: usize,
+    // Calls accepted and not finished yet, running or waiting
+    calls: Arc<AtomicUsize>,
+}
+
+impl BindingQueue {
+    fn new(max_concurrency: usize) -> Self {
+        let (send, mut recv) = mpsc::unbounded_channel::<BindingCall>();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_worker = Arc::clone(&calls);
+        let slots = Arc::new(tokio::sync::Semaphore::new(max_concurrency));
+        tokio::spawn(async move {
+            while let Some(call) = recv.recv().await {
+                let permit = match Arc::clone(&slots).acquire_owned().await {
+                    Ok(permit) => permit,
+                    Err(_) => break,
+                };
+                let calls = Arc::clone(&calls_worker);
+                tokio::spawn(async move {
+                    call.await;
+                    calls.fetch_sub(1, Ordering::SeqCst);
+                    drop(permit);
+                });
+            }
+        });
+        BindingQueue {
+            send,
+            max_concurrency,
+            calls,
+        }
+    }
+
+    /// Queue a call, unless `max_queue` calls are already waiting
+    pub fn push(&self, call: BindingCall, max_queue: Option<usize>) -> bool {
+        let calls = self.calls.fetch_add(1, Ordering::SeqCst);
+        if max_queue.is_some_and(|max| calls >= self.max_concurrency + max)
+            || self.send.send(call).is_err()
+        {
+            self.calls.fetch_sub(1, Ordering::SeqCst);
+            return false;
+        }
+        true
+    }
+}
+
 /// One browser window (a devtools target with its own session).
 pub struct Window {
     chrome: Arc<Chrome>,
     target: String,
     session: String,
     window_id: AtomicI32,
-    bindings: dashmap::DashMap<String, BindingFunc>,
+    bindings: dashmap::DashMap<String, Binding>,
+    origin_policy: std::sync::RwLock<OriginPolicy>,
+    blocked_calls: broadcast::Sender<BlockedCall>,
+    // Scripts added by load_js, replayed into child sessions as they attach
+    scripts: std::sync::Mutex<Vec<String>>,
+    children: dashmap::DashMap<String, ChildSession>,
+    // Keyed by session and executionContextId: context ids are only unique
+    // within one target.
+    contexts: dashmap::DashMap<(String, i64), ExecutionContext>,
+    // Current url of every frame by frame id, from all sessions
+    frames: dashmap::DashMap<String, String>,
+    // CSS added by load_css, added again when hot reload reloads the window
+    styles: std::sync::Mutex<Vec<String>>,
+    // Stylesheets loaded from urls, keyed by session and styleSheetId
+    style_sheets: dashmap::DashMap<(String, String), StyleSheet>,
+    // Fetch interception routes, the first match handles a request
+    routes: std::sync::RwLock<Vec<Route>>,
+    navigation_policy: std::sync::RwLock<Option<PolicyFunc>>,
+    new_window_handler: std::sync::RwLock<Option<NewWindowFunc>>,
+    log_sink: Option<Arc<LogSink>>,
+    // Bounds set in headless mode, emulated with the viewport size
+    viewport: std::sync::Mutex<Option<Bounds>>,
+    // Asked before the user closes or leaves the page
+    close_handler: std::sync::RwLock<Option<CloseFunc>>,
+    // Navigations started by alcro itself (e.g. load) in progress; the
+    // navigation policy does not apply to them.
+    own_navigations: std::sync::Mutex<Vec<OwnNavigation>>,
+    // HTML written into the page by load, so reload can write it again.
+    // Cleared when the main frame navigates.
+    inline_html: std::sync::Mutex<Option<String>>,
+    // Flat session holding a window opened by a page before it starts,
+    // until its new window handler returns; see popup_opened.
+    waiting_session: std::sync::Mutex<Option<String>>,
     load_send: mpsc::UnboundedSender<LoadEvent>,
     load_recv: Mutex<mpsc::UnboundedReceiver<LoadEvent>>,
+    navigation_events: broadcast::Sender<NavigationEvent>,
+    window_events: broadcast::Sender<WindowEvent>,
+    // Visibility in the latest report of the observer script
+    window_reports: watch::Sender<bool>,
     closed_tx: watch::Sender<bool>,
     closed_rx: watch::Receiver<bool>,
 }
@@ -153,6 +745,218 @@ pub enum WindowState {
     Fullscreen,
 }
 
+/// A rectangle on the screen, in device independent pixels
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    /// The area this rectangle shares with `other`, 0 if they do not overlap
+    pub fn overlap(&self, other: &Rect) -> i64 {
+        let width =
+            (self.left + self.width).min(other.left + other.width) - self.left.max(other.left);
+        let height =
+            (self.top + self.height).min(other.top + other.height) - self.top.max(other.top);
+        if width <= 0 || height <= 0 {
+            0
+        } else {
+            width as i64 * height as i64
+        }
+    }
+}
+
+/// A display (monitor) connected to the computer, see
+/// [`UI::displays()`](crate::UI::displays).
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Debug, PartialEq, Clone)]
+pub struct DisplayInfo {
+    /// The name of the display, empty if unknown
+    pub label: String,
+    /// The whole display
+    pub bounds: Rect,
+    /// The part of the display available to windows, without e.g. the taskbar
+    pub work_area: Rect,
+    /// Device pixels per device independent pixel
+    pub scale_factor: f64,
+    /// Whether this is the primary display
+    pub primary: bool,
+}
+
+impl Bounds {
+    /// The position and size of the window, without its state
+    pub fn rect(&self) -> Rect {
+        Rect {
+            left: self.left,
+            top: self.top,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// These bounds moved to the middle of the work area of `display`, in the normal
+    /// window state. The window shrinks to the work area if it is larger.
+    pub fn center_on(&self, display: &DisplayInfo) -> Bounds {
+        let area = display.work_area;
+        let width = self.width.min(area.width);
+        let height = self.height.min(area.height);
+        Bounds {
+            left: area.left + (area.width - width) / 2,
+            top: area.top + (area.height - height) / 2,
+            width,
+            height,
+            window_state: WindowState::Normal,
+        }
+    }
+
+    /// These bounds moved, and shrunk if needed, to lie within the work area of a
+    /// display: the one they overlap most, or the primary display if they are not
+    /// visible on any, e.g. because their display was disconnected. They are returned
+    /// unchanged if there are no displays.
+    pub fn clamp_to(&self, displays: &[DisplayInfo]) -> Bounds {
+        let rect = self.rect();
+        let display = displays
+            .iter()
+            .filter(|d| d.work_area.overlap(&rect) > 0)
+            .max_by_key(|d| d.work_area.overlap(&rect))
+            .or_else(|| displays.iter().find(|d| d.primary))
+            .or_else(|| displays.first());
+        let Some(display) = display else {
+            return *self;
+        };
+        let area = display.work_area;
+        let width = self.width.min(area.width);
+        let height = self.height.min(area.height);
+        Bounds {
+            left: self.left.clamp(area.left, area.left + area.width - width),
+            top: self.top.clamp(area.top, area.top + area.height - height),
+            width,
+            height,
+            window_state: self.window_state,
+        }
+    }
+}
+
+/// A device to emulate with [`UI::emulate()`](crate::UI::emulate): its screen,
+/// input and browser.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DeviceProfile {
+    /// Width of the viewport in device independent pixels, 0 keeps the window's
+    pub width: i32,
+    /// Height of the viewport in device independent pixels, 0 keeps the window's
+    pub height: i32,
+    /// Device pixels per device independent pixel, 0 keeps the screen's
+    pub device_scale_factor: f64,
+    /// Emulate a mobile device: the page's meta viewport is honored and
+    /// scrollbars overlay the content
+    pub mobile: bool,
+    /// Emulate a touch screen
+    pub touch: bool,
+    /// The user agent string, None keeps the browser's
+    pub user_agent: Option<String>,
+    /// Zoom factor, like the browser's zoom: the page is laid out with `width / zoom`
+    /// CSS pixels and its `devicePixelRatio` is multiplied by `zoom`. With a
+    /// `device_scale_factor` of 0 the screen's ratio is kept as is.
+    pub zoom: f64,
+}
+
+const IPHONE_USER_AGENT: &str = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) \
+    AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1";
+const IPAD_USER_AGENT: &str = "Mozilla/5.0 (iPad; CPU OS 17_0 like Mac OS X) \
+    AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1";
+const ANDROID_USER_AGENT: &str = "Mozilla/5.0 (Linux; Android 14; Pixel 7) \
+    AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36";
+
+impl DeviceProfile {
+    /// A desktop screen of the given size and scale factor, with a mouse
+    pub fn desktop(width: i32, height: i32, device_scale_factor: f64) -> Self {
+        DeviceProfile {
+            width,
+            height,
+            device_scale_factor,
+            mobile: false,
+            touch: false,
+            user_agent: None,
+            zoom: 1.0,
+        }
+    }
+
+    /// A full HD desktop screen
+    pub fn full_hd() -> Self {
+        Self::desktop(1920, 1080, 1.0)
+    }
+
+    /// A laptop with a high DPI screen
+    pub fn hidpi_laptop() -> Self {
+        Self::desktop(1440, 900, 2.0)
+    }
+
+    /// A small phone (iPhone SE)
+    pub fn iphone_se() -> Self {
+        Self::mobile(375, 667, 2.0, IPHONE_USER_AGENT)
+    }
+
+    /// A current phone (iPhone 15)
+    pub fn iphone_15() -> Self {
+        Self::mobile(393, 852, 3.0, IPHONE_USER_AGENT)
+    }
+
+    /// An Android phone (Pixel 7)
+    pub fn pixel_7() -> Self {
+        Self::mobile(412, 915, 2.625, ANDROID_USER_AGENT)
+    }
+
+    /// A tablet (iPad Mini) in portrait orientation
+    pub fn ipad_mini() -> Self {
+        Self::mobile(768, 1024, 2.0, IPAD_USER_AGENT)
+    }
+
+    fn mobile(width: i32, height: i32, device_scale_factor: f64, user_agent: &str) -> Self {
+        DeviceProfile {
+            width,
+            height,
+            device_scale_factor,
+            mobile: true,
+            touch: true,
+            user_agent: Some(user_agent.to_string()),
+            zoom: 1.0,
+        }
+    }
+
+    /// Swap width and height, e.g. to turn a phone to landscape orientation
+    pub fn rotate(mut self) -> Self {
+        std::mem::swap(&mut self.width, &mut self.height);
+        self
+    }
+
+    /// Set the zoom factor
+    pub fn zoom(mut self, zoom: f64) -> Self {
+        self.zoom = zoom;
+        self
+    }
+
+    /// The Emulation.setDeviceMetricsOverride parameters. Zooming shrinks the
+    /// CSS viewport and raises the device pixel ratio by the same factor.
+    fn metrics(&self) -> JSObject {
+        let zoom = if self.zoom > 0.0 { self.zoom } else { 1.0 };
+        let scale = |px: i32| (px as f64 / zoom).round() as i64;
+        // A scale factor of 0 keeps the screen's, which cannot be zoomed here
+        let dpr = self.device_scale_factor * zoom;
+        json!({
+            "width": scale(self.width),
+            "height": scale(self.height),
+            "deviceScaleFactor": dpr,
+            "mobile": self.mobile,
+        })
+    }
+}
+
 impl WindowState {
     /// Convert to Bounds struct
     pub fn to_bounds(self) -> Bounds {
@@ -168,21 +972,57 @@ impl WindowState {
 
 impl Chrome {
     fn log(&self, msg: &JSObject) {
-        use std::io::Write;
-        match &self.log_sink {
-            None => {}
-            Some(LogSink::Stdout) => println!("Message: {}", msg),
-            Some(LogSink::Stderr) => eprintln!("Message: {}", msg),
-            Some(LogSink::File(f)) => {
-                let mut f = f.lock().expect("Unable to lock");
-                let _ = writeln!(f, "Message: {}", msg);
-            }
+        if let Some(sink) = &self.log_sink {
+            sink.write(msg);
         }
     }
 
     fn kill_process(&self) {
         let _ = kill_proc(self.pid as Process);
     }
+
+    /// Whether the browser process is still needed: it has windows, one is
+    /// being created, or a Browser handle keeps it alive.
+    fn needed(&self) -> bool {
+        !self.windows.is_empty()
+            || self.windows_in_creation.load(Ordering::SeqCst) > 0
+            || self.keep_alive.load(Ordering::SeqCst) > 0
+    }
+
+    /// Keep the browser running while it has no windows, until `release`.
+    pub fn retain(&self) {
+        self.keep_alive.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Undo a `retain`. Without windows left the browser process is killed.
+    pub fn release(&self) {
+        self.keep_alive.fetch_sub(1, Ordering::SeqCst);
+        self.windows
+            .retain(|_, w| w.upgrade().is_some_and(|w| !w.is_closed()));
+        if !self.needed() && !self.closed.load(Ordering::Relaxed) {
+            self.kill_process();
+        }
+    }
+
+    /// The open windows that still have a handle
+    pub fn windows(&self) -> Vec<Arc<Window>> {
+        self.windows
+            .iter()
+            .filter_map(|e| e.value().upgrade())
+            .filter(|w| !w.is_closed())
+            .collect()
+    }
+
+    /// Returns true if the browser has exited
+    pub fn is_closed(&self) -> bool {
+        *self.exited.borrow()
+    }
+
+    /// Wait until the browser has exited
+    pub async fn wait_exit(&self) {
+        let mut rx = self.exited.subscribe();
+        let _ = rx.wait_for(|exited| *exited).await;
+    }
 }
 
 /// The browser process is killed when the last reference to it (via its
@@ -211,57 +1051,204 @@ impl Window {
     /// Returns true if any other window of the same browser is still open
     pub fn has_other_live_windows(&self) -> bool {
         self.chrome.windows.iter().any(|e| {
-            e.key() != &self.session
-                && e.value()
-                    .upgrade()
-                    .map_or(false, |w| !w.is_closed())
+            e.key() != &self.session && e.value().upgrade().is_some_and(|w| !w.is_closed())
         })
     }
 
+    /// Set the origin policy applied to all bindings of this window
+    pub fn set_origin_policy(&self, policy: OriginPolicy) {
+        *self.origin_policy.write().expect("Unable to lock") = policy;
+    }
+
+    pub fn origin_policy(&self) -> OriginPolicy {
+        self.origin_policy.read().expect("Unable to lock").clone()
+    }
+
+    /// The settings windows opened from this one start with
+    pub fn inherited_config(&self) -> WindowConfig {
+        WindowConfig {
+            origin_policy: self.origin_policy(),
+            routes: self
+                .routes
+                .read()
+                .expect("Unable to lock")
+                .iter()
+                .filter(|r| r.inherit)
+                .cloned()
+                .collect(),
+            navigation_policy: self
+                .navigation_policy
+                .read()
+                .expect("Unable to lock")
+                .clone(),
+            new_window_handler: self
+                .new_window_handler
+                .read()
+                .expect("Unable to lock")
+                .clone(),
+            log_sink: self.log_sink.clone(),
+            load_options: LoadOptions::default(),
+        }
+    }
+
+    /// Set the handler for windows the page opens, see popup_opened. Without
+    /// one they are left alone.
+    pub async fn set_new_window_handler(
+        &self,
+        handler: Option<NewWindowFunc>,
+    ) -> Result<(), JSError> {
+        *self.new_window_handler.write().expect("Unable to lock") = handler;
+        update_auto_attach(&self.chrome)
+            .await
+            .to_result_of_jserror()
+    }
+
+    /// Subscribe to binding calls rejected by an origin policy
+    pub fn blocked_calls(&self) -> broadcast::Receiver<BlockedCall> {
+        self.blocked_calls.subscribe()
+    }
+
+    pub fn navigation_events(&self) -> broadcast::Receiver<NavigationEvent> {
+        self.navigation_events.subscribe()
+    }
+
+    fn notify(&self, event: NavigationEvent) {
+        // Nobody listening is not an error
+        let _ = self.navigation_events.send(event);
+    }
+
+    pub fn window_events(&self) -> broadcast::Receiver<WindowEvent> {
+        self.window_events.subscribe()
+    }
+
+    /// Returns true if a Browser handle keeps the browser running without
+    /// windows
+    pub fn browser_kept_alive(&self) -> bool {
+        self.chrome.keep_alive.load(Ordering::SeqCst) > 0
+    }
+
+    /// Log to the window's own log sink, or else the browser's
+    fn log(&self, msg: &JSObject) {
+        match &self.log_sink {
+            Some(sink) => sink.write(msg),
+            None => self.chrome.log(msg),
+        }
+    }
+
+    /// Keep a window opened by a page from ever starting, if it is still
+    /// waiting for its new window handler.
+    pub fn cancel_start(&self) {
+        self.waiting_session.lock().expect("Unable to lock").take();
+    }
+
     /// Synchronous best-effort kill of the whole browser, for use in Drop.
     pub fn kill_browser(&self) {
         self.chrome.kill_process();
     }
 }
 
-/// Launch the browser process and return its first window.
+/// Launch the browser process and return its first window. A `hidden` window
+/// is launched outside of the displays; where the window manager does not
+/// leave it there it is minimized before its content loads, see
+/// restore_window and show_window.
 pub async fn launch(
     chrome_binary: &str,
     args: &[&str],
-    url: &str,
+    source: &Source,
+    config: &WindowConfig,
+    hidden: bool,
     log_sink: Option<LogSink>,
     tmpdir: Option<tempfile::TempDir>,
-) -> Result<Arc<Window>, JSError> {
+) -> Result<Arc<Window>, LoadError> {
     let (pid, read_file, write_file) =
-        new_process(chrome_binary, &args).expect("Unable to launch chrome");
+        new_process(chrome_binary, args).expect("Unable to launch chrome");
     let mut precv = PipeReader::new(read_file).expect("Unable to open browser pipe");
     let mut psend = PipeWriter::new(write_file).expect("Unable to open browser pipe");
 
     let target = find_target(&mut psend, &mut precv).await;
     let session = start_session(&mut psend, &mut precv, &target).await?;
 
-    let c_arc = Arc::new(Chrome {
-        id: AtomicI32::new(2),
-        psend: Mutex::new(psend),
-        pending: dashmap::DashMap::new(),
-        pending_browser: dashmap::DashMap::new(),
-        windows: dashmap::DashMap::new(),
-        windows_in_creation: AtomicI32::new(0),
-        headless: args.contains(&"--headless"),
-        log_sink,
+    let c_arc = new_chrome(pid, psend, args, log_sink, tmpdir);
+    let window = register_window(&c_arc, target, session, config);
+    tokio::spawn(readloop(Arc::clone(&c_arc), precv));
+    update_auto_attach(&c_arc).await.map_err(JSError::from)?;
+
+    enable_window(&window).await?;
+    if hidden && is_on_display(&window).await? {
+        set_bounds(&window, WindowState::Minimized.to_bounds()).await?;
+    }
+    load(&window, source, &config.load_options).await?;
+    Ok(window)
+}
+
+/// Launch the browser process without a window. It keeps running until it is
+/// closed or `release`d, even without windows.
+pub async fn launch_browser(
+    chrome_binary: &str,
+    args: &[&str],
+    log_sink: Option<LogSink>,
+    tmpdir: Option<tempfile::TempDir>,
+) -> Result<Arc<Chrome>, JSError> {
+    let (pid, read_file, write_file) =
+        new_process(chrome_binary, args).expect("Unable to launch chrome");
+    let precv = PipeReader::new(read_file).expect("Unable to open browser pipe");
+    let psend = PipeWriter::new(write_file).expect("Unable to open browser pipe");
+
+    let c_arc = new_chrome(pid, psend, args, log_sink, tmpdir);
+    c_arc.retain();
+    tokio::spawn(readloop(Arc::clone(&c_arc), precv));
+    // Target.targetDestroyed events tell the read loop about closed windows
+    if let Err(e) = send_browser(
+        &c_arc,
+        "Target.setDiscoverTargets",
+        &json!({ "discover": true }),
+    )
+    .await
+    {
+        c_arc.kill_process();
+        return Err(e.into());
+    }
+    // Without a window the browser would exit, so a page that is not shown
+    // anywhere keeps it running. It is gone with the pipe.
+    if let Err(e) = send_browser(
+        &c_arc,
+        "Target.createTarget",
+        &json!({ "url": "about:blank", "hidden": true, "background": true }),
+    )
+    .await
+    {
+        c_arc.kill_process();
+        return Err(e.into());
+    }
+    Ok(c_arc)
+}
+
+fn new_chrome(
+    pid: Process,
+    psend: PipeWriter,
+    args: &[&str],
+    log_sink: Option<LogSink>,
+    tmpdir: Option<tempfile::TempDir>,
+) -> Arc<Chrome> {
+    Arc::new(Chrome {
+        id: AtomicI32::new(2),
+        psend: Mutex::new(psend),
+        pending: dashmap::DashMap::new(),
+        pending_browser: dashmap::DashMap::new(),
+        windows: dashmap::DashMap::new(),
+        windows_in_creation: AtomicI32::new(0),
+        auto_attach: Mutex::new(false),
+        keep_alive: AtomicUsize::new(0),
+        headless: args.contains(&"--headless"),
+        log_sink,
         closed: AtomicBool::new(false),
+        exited: watch::channel(false).0,
         _tmpdir: tmpdir,
         #[cfg(target_family = "windows")]
         pid: pid as usize,
         #[cfg(target_family = "unix")]
         pid,
-    });
-
-    let window = register_window(&c_arc, target, session);
-    tokio::spawn(readloop(Arc::clone(&c_arc), precv));
-
-    init_window(&window, url).await?;
-    Ok(window)
+    })
 }
 
 /// Decrements `windows_in_creation` even when window creation is cancelled at
@@ -273,10 +1260,7 @@ struct CreationGuard<'a>(&'a Chrome);
 impl Drop for CreationGuard<'_> {
     fn drop(&mut self) {
         self.0.windows_in_creation.fetch_sub(1, Ordering::SeqCst);
-        if self.0.windows.is_empty()
-            && self.0.windows_in_creation.load(Ordering::SeqCst) == 0
-            && !self.0.closed.load(Ordering::Relaxed)
-        {
+        if !self.0.needed() && !self.0.closed.load(Ordering::Relaxed) {
             self.0.kill_process();
         }
     }
@@ -286,18 +1270,34 @@ impl Drop for CreationGuard<'_> {
 ///
 /// If the returned future is cancelled at an await point the browser may be
 /// left with an untracked window until the browser process exits.
-pub async fn new_window(w: &Arc<Window>, url: &str) -> Result<Arc<Window>, JSError> {
-    let c = &w.chrome;
+pub async fn new_window(
+    w: &Arc<Window>,
+    source: &Source,
+    config: &WindowConfig,
+    options: &WindowOptions,
+) -> Result<Arc<Window>, LoadError> {
+    open_window(&w.chrome, source, config, options).await
+}
+
+/// Open a window in the browser process `c`, see `new_window`.
+pub async fn open_window(
+    c: &Arc<Chrome>,
+    source: &Source,
+    config: &WindowConfig,
+    options: &WindowOptions,
+) -> Result<Arc<Window>, LoadError> {
     c.windows_in_creation.fetch_add(1, Ordering::SeqCst);
     let _guard = CreationGuard(c);
-    create_window(c, url).await
+    create_window(c, source, config, options).await
 }
 
-async fn create_window(c: &Arc<Chrome>, url: &str) -> Result<Arc<Window>, JSError> {
-    let mut params = json!({ "url": "about:blank" });
-    if !c.headless {
-        params["newWindow"] = json!(true);
-    }
+async fn create_window(
+    c: &Arc<Chrome>,
+    source: &Source,
+    config: &WindowConfig,
+    options: &WindowOptions,
+) -> Result<Arc<Window>, LoadError> {
+    let params = options.target_params(c.headless);
     let target = send_browser(c, "Target.createTarget", &params)
         .await
         .map_err(JSError::from)?["targetId"]
@@ -305,7 +1305,7 @@ async fn create_window(c: &Arc<Chrome>, url: &str) -> Result<Arc<Window>, JSErro
         .expect("Value not of string datatype")
         .to_string();
 
-    match attach_window(c, &target, url).await {
+    match attach_window(c, &target, source, config, options).await {
         Ok(window) => Ok(window),
         Err(e) => {
             // Roll back so a half-created window does not linger as an
@@ -325,7 +1325,13 @@ async fn create_window(c: &Arc<Chrome>, url: &str) -> Result<Arc<Window>, JSErro
     }
 }
 
-async fn attach_window(c: &Arc<Chrome>, target: &str, url: &str) -> Result<Arc<Window>, JSError> {
+async fn attach_window(
+    c: &Arc<Chrome>,
+    target: &str,
+    source: &Source,
+    config: &WindowConfig,
+    options: &WindowOptions,
+) -> Result<Arc<Window>, LoadError> {
     let session = send_browser(c, "Target.attachToTarget", &json!({ "targetId": target }))
         .await
         .map_err(JSError::from)?["sessionId"]
@@ -333,15 +1339,186 @@ async fn attach_window(c: &Arc<Chrome>, target: &str, url: &str) -> Result<Arc<W
         .expect("Value not of string datatype")
         .to_string();
 
-    let window = register_window(c, target.to_string(), session);
-    if let Err(e) = init_window(&window, url).await {
+    let window = register_window(c, target.to_string(), session, config);
+    let res = async {
+        update_auto_attach(c).await.map_err(JSError::from)?;
+        enable_window(&window).await?;
+        place_window(&window, options).await?;
+        load(&window, source, &config.load_options).await
+    }
+    .await;
+    if let Err(e) = res {
         c.windows.remove(&window.session);
         return Err(e);
     }
     Ok(window)
 }
 
-fn register_window(c: &Arc<Chrome>, target: String, session: String) -> Arc<Window> {
+/// Give a new window the size, position and state of `options`, where the
+/// browser did not already apply them in Target.createTarget (older versions
+/// do not support all of them).
+async fn place_window(w: &Arc<Window>, options: &WindowOptions) -> Result<(), JSError> {
+    if options.tab || (w.chrome.headless && options.size.is_none()) {
+        return Ok(());
+    }
+    let current = bounds(w).await.map_err(JSError::from)?;
+    if current.window_state == WindowState::Normal {
+        let mut wanted = current;
+        if let Some((width, height)) = options.size {
+            wanted.width = width;
+            wanted.height = height;
+        }
+        if let Some((left, top)) = options.position {
+            wanted.left = left;
+            wanted.top = top;
+        }
+        if wanted != current {
+            set_bounds(w, wanted).await?;
+        }
+    }
+    // Without a state the window is left in the one the browser opened it in.
+    // Headless windows only have a viewport, see set_bounds.
+    if let Some(state) = options.state {
+        if current.window_state != state && !w.chrome.headless {
+            set_bounds(w, state.to_bounds()).await?;
+        }
+    }
+    Ok(())
+}
+
+/// While a window has a new window handler, make the browser attach to every
+/// new page and hold it before it loads anything, so that windows opened by a
+/// page can be set up before they start, see popup_opened. Otherwise pages
+/// start untouched. Browser level auto-attach only works in flat mode.
+pub async fn update_auto_attach(c: &Arc<Chrome>) -> JSResult {
+    let mut auto_attach = c.auto_attach.lock().await;
+    let wanted = c.windows.iter().any(|e| {
+        e.value().upgrade().is_some_and(|w| {
+            w.new_window_handler
+                .read()
+                .expect("Unable to lock")
+                .is_some()
+        })
+    });
+    if wanted == *auto_attach {
+        return Ok(JSObject::Null);
+    }
+    let res = send_browser(
+        c,
+        "Target.setAutoAttach",
+        &json!({
+            "autoAttach": wanted,
+            "waitForDebuggerOnStart": wanted,
+            "flatten": true,
+            "filter": [{ "type": "page" }],
+        }),
+    )
+    .await?;
+    *auto_attach = wanted;
+    Ok(res)
+}
+
+/// Let a page held by update_auto_attach start, and detach from it.
+pub async fn resume_page(c: &Arc<Chrome>, paused: &str) {
+    let _ = send_flat(
+        c,
+        paused,
+        "Runtime.runIfWaitingForDebugger",
+        &JSObject::Null,
+    )
+    .await;
+    let _ = send_browser(
+        c,
+        "Target.detachFromTarget",
+        &json!({ "sessionId": paused }),
+    )
+    .await;
+}
+
+/// Handle a new page held by update_auto_attach. If the page of `opener`
+/// opened it (with window.open or a link to a new window), attach to it with
+/// the opener's config, bindings and scripts and hand it to the opener's new
+/// window handler. The window starts once the handler returns, unless the
+/// handler closed it: a denied window never loads anything. Other pages, and
+/// windows of an opener without a handler, start right away and are left
+/// alone.
+pub fn popup_opened(c: &Arc<Chrome>, opener: Option<Arc<Window>>, target: String, paused: String) {
+    let handler = opener.as_ref().and_then(|opener| {
+        opener
+            .new_window_handler
+            .read()
+            .expect("Unable to lock")
+            .clone()
+    });
+    let c = Arc::clone(c);
+    let (Some(opener), Some(handler)) = (opener, handler) else {
+        tokio::spawn(async move { resume_page(&c, &paused).await });
+        return;
+    };
+    c.windows_in_creation.fetch_add(1, Ordering::SeqCst);
+    tokio::spawn(async move {
+        let guard = CreationGuard(&c);
+        let popup = match attach_popup(&opener, &target, &paused).await {
+            Ok(popup) => popup,
+            Err(e) => {
+                eprintln!("Unable to attach to new window: {}", e);
+                resume_page(&c, &paused).await;
+                return;
+            }
+        };
+        drop(guard);
+        handler(Arc::clone(&popup)).await;
+        let paused = popup.waiting_session.lock().expect("Unable to lock").take();
+        if let Some(paused) = paused {
+            resume_page(&c, &paused).await;
+        }
+    });
+}
+
+async fn attach_popup(
+    opener: &Arc<Window>,
+    target: &str,
+    paused: &str,
+) -> Result<Arc<Window>, JSError> {
+    let c = &opener.chrome;
+    let session = send_browser(c, "Target.attachToTarget", &json!({ "targetId": target })).await?
+        ["sessionId"]
+        .as_str()
+        .expect("Value not of string datatype")
+        .to_string();
+    let popup = register_window(c, target.to_string(), session, &opener.inherited_config());
+    *popup.waiting_session.lock().expect("Unable to lock") = Some(paused.to_string());
+    let res = async {
+        enable_window(&popup).await?;
+        let bindings: Vec<(String, BindingFunc, BindingOptions)> = opener
+            .bindings
+            .iter()
+            .map(|b| (b.key().clone(), Arc::clone(&b.func), b.options.clone()))
+            .collect();
+        for (name, func, options) in bindings {
+            bind(&popup, &name, func, options).await?;
+        }
+        let scripts = opener.scripts.lock().expect("Unable to lock").clone();
+        for script in scripts {
+            load_js(&popup, &script).await?;
+        }
+        Ok(())
+    }
+    .await;
+    if let Err(e) = res {
+        popup.cancel_start();
+        c.windows.remove(&popup.session);
+        return Err(e);
+    }
+    Ok(popup)
+}
+
+fn register_window(
+    c: &Arc<Chrome>,
+    target: String,
+    session: String,
+    config: &WindowConfig,
+) -> Arc<Window> {
     let (load_send, load_recv) = mpsc::unbounded_channel();
     let (closed_tx, closed_rx) = watch::channel(false);
     let window = Arc::new(Window {
@@ -350,8 +1527,28 @@ fn register_window(c: &Arc<Chrome>, target: String, session: String) -> Arc<Wind
         session: session.clone(),
         window_id: AtomicI32::new(0),
         bindings: dashmap::DashMap::new(),
+        origin_policy: std::sync::RwLock::new(config.origin_policy.clone()),
+        blocked_calls: broadcast::channel(16).0,
+        scripts: std::sync::Mutex::new(Vec::new()),
+        children: dashmap::DashMap::new(),
+        contexts: dashmap::DashMap::new(),
+        frames: dashmap::DashMap::new(),
+        styles: std::sync::Mutex::new(Vec::new()),
+        style_sheets: dashmap::DashMap::new(),
+        routes: std::sync::RwLock::new(config.routes.clone()),
+        navigation_policy: std::sync::RwLock::new(config.navigation_policy.clone()),
+        new_window_handler: std::sync::RwLock::new(config.new_window_handler.clone()),
+        log_sink: config.log_sink.clone(),
+        viewport: std::sync::Mutex::new(None),
+        close_handler: std::sync::RwLock::new(None),
+        own_navigations: std::sync::Mutex::new(Vec::new()),
+        inline_html: std::sync::Mutex::new(None),
+        waiting_session: std::sync::Mutex::new(None),
         load_send,
         load_recv: Mutex::new(load_recv),
+        navigation_events: broadcast::channel(16).0,
+        window_events: broadcast::channel(16).0,
+        window_reports: watch::channel(true).0,
         closed_tx,
         closed_rx,
     });
@@ -359,13 +1556,17 @@ fn register_window(c: &Arc<Chrome>, target: String, session: String) -> Arc<Wind
     window
 }
 
-/// Enable the devtools domains on a fresh session and load the initial url.
-async fn init_window(w: &Arc<Window>, url: &str) -> Result<(), JSError> {
+/// Enable the devtools domains on a fresh session.
+async fn enable_window(w: &Arc<Window>) -> Result<(), JSError> {
     for (method, params) in [
         ("Page.enable", JSObject::Null),
+        ("Page.setLifecycleEventsEnabled", json!({ "enabled": true })),
+        // Child targets (out-of-process iframes) start paused so that
+        // bindings and scripts are in place before their documents run; see
+        // init_child.
         (
             "Target.setAutoAttach",
-            json!({"autoAttach": true, "waitForDebuggerOnStart": false}),
+            json!({"autoAttach": true, "waitForDebuggerOnStart": true, "flatten": false}),
         ),
         ("Network.enable", JSObject::Null),
         ("Runtime.enable", JSObject::Null),
@@ -377,7 +1578,10 @@ async fn init_window(w: &Arc<Window>, url: &str) -> Result<(), JSError> {
     ]
     .iter()
     {
-        send(w, method, params).await?;
+        send(w, method, params).await.map_err(JSError::from)?;
+    }
+    if intercepts(w) {
+        enable_fetch(w, &w.session).await.map_err(JSError::from)?;
     }
 
     if !w.chrome.headless {
@@ -392,8 +1596,8 @@ async fn init_window(w: &Arc<Window>, url: &str) -> Result<(), JSError> {
             .expect("Value not i64") as i32;
         w.window_id.store(win_id, Ordering::Relaxed);
     }
-
-    load(w, url).await
+    observe_window(w).await.map_err(JSError::from)?;
+    Ok(())
 }
 
 async fn find_target(psend: &mut PipeWriter, precv: &mut PipeReader) -> String {
@@ -463,36 +1667,383 @@ async fn start_session(
     }
 }
 
-pub async fn load(w: &Arc<Window>, url: &str) -> Result<(), JSError> {
+/// Load `source` and wait for it as `options` say.
+pub async fn load(
+    w: &Arc<Window>,
+    source: &Source,
+    options: &LoadOptions,
+) -> Result<(), LoadError> {
+    tokio::time::timeout(options.timeout, async {
+        match source {
+            Source::Url(url) => navigate(w, url, &options.wait_until).await,
+            Source::Assets(provider) => {
+                set_route(w, assets_route(Arc::clone(provider)))
+                    .await
+                    .map_err(JSError::from)?;
+                navigate(w, &format!("{}/", ASSETS_ORIGIN), &options.wait_until).await
+            }
+            Source::Html { html, base_url } => {
+                navigate(w, base_url, &options.wait_until).await?;
+                set_document(w, html, &options.wait_until).await?;
+                *w.inline_html.lock().expect("Unable to lock") = Some(html.clone());
+                Ok(())
+            }
+        }
+    })
+    .await
+    .map_err(|_| LoadError::Timeout(options.timeout))?
+}
+
+/// Replace the document of the main frame with `html`, keeping its url, and
+/// wait for it. Lifecycle events do not report documents written this way, so
+/// the wait goes by `document.readyState`.
+async fn set_document(w: &Arc<Window>, html: &str, until: &WaitUntil) -> Result<(), LoadError> {
+    send(
+        w,
+        "Page.setDocumentContent",
+        &json!({ "frameId": w.target, "html": html }),
+    )
+    .await
+    .map_err(JSError::from)?;
+    let ready = match until {
+        WaitUntil::DomContentLoaded => {
+            "document.readyState !== 'loading' || \
+             new Promise(r => addEventListener('DOMContentLoaded', () => r(true), { once: true }))"
+        }
+        WaitUntil::Load | WaitUntil::NetworkIdle => {
+            "document.readyState === 'complete' || \
+             new Promise(r => addEventListener('load', () => r(true), { once: true }))"
+        }
+        WaitUntil::Predicate(_) => "true",
+    };
+    eval(w, ready).await.map_err(JSError::from)?;
+    // Writing the document removed the observer's event listeners
+    run_observer(w).await.map_err(JSError::from)?;
+    if let WaitUntil::Predicate(predicate) = until {
+        wait_for_predicate(w, predicate).await;
+    }
+    Ok(())
+}
+
+/// A navigation of the main frame started by alcro. Its request is recognized
+/// by its url, and then by its network id, which it keeps across redirects.
+/// Until its request starts it may ask the current document to unload once.
+pub struct OwnNavigation {
+    id: usize,
+    url: String,
+    network_id: Option<String>,
+    unloading: bool,
+}
+
+static NEXT_NAVIGATION: AtomicUsize = AtomicUsize::new(0);
+
+/// Registers a navigation to `url` started by alcro while it is alive.
+struct ProgrammaticLoad<'a>(&'a Window, usize);
+impl<'a> ProgrammaticLoad<'a> {
+    fn new(w: &'a Window, url: &str) -> Self {
+        let id = NEXT_NAVIGATION.fetch_add(1, Ordering::Relaxed);
+        w.own_navigations
+            .lock()
+            .expect("Unable to lock")
+            .push(OwnNavigation {
+                id,
+                url: navigation_url(url),
+                network_id: None,
+                unloading: true,
+            });
+        ProgrammaticLoad(w, id)
+    }
+}
+impl Drop for ProgrammaticLoad<'_> {
+    fn drop(&mut self) {
+        self.0
+            .own_navigations
+            .lock()
+            .expect("Unable to lock")
+            .retain(|n| n.id != self.1);
+    }
+}
+
+/// Turns the cache of a window back on when dropped, also if the load it was
+/// disabled for is cancelled.
+struct CacheDisabled(Arc<Window>);
+impl Drop for CacheDisabled {
+    fn drop(&mut self) {
+        let w = Arc::clone(&self.0);
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(async move {
+                let _ = send(
+                    &w,
+                    "Network.setCacheDisabled",
+                    &json!({ "cacheDisabled": false }),
+                )
+                .await;
+            });
+        }
+    }
+}
+
+/// `url` the way it appears in the request of a navigation: without its
+/// fragment, with a lowercase scheme and host, and with a path.
+fn navigation_url(url: &str) -> String {
+    let url = url.split('#').next().unwrap_or("");
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+    let (host, path) = rest.split_at(rest.find(['/', '?']).unwrap_or(rest.len()));
+    let slash = if path.starts_with('/') { "" } else { "/" };
+    format!(
+        "{}://{}{}{}",
+        scheme.to_ascii_lowercase(),
+        host.to_ascii_lowercase(),
+        slash,
+        path
+    )
+}
+
+/// Returns true if the main frame request `url` with `network_id` belongs to
+/// a navigation started by alcro. Requests of other navigations that start
+/// meanwhile, e.g. by the page, do not match.
+pub fn is_own_navigation(w: &Window, url: &str, network_id: &str) -> bool {
+    let mut navigations = w.own_navigations.lock().expect("Unable to lock");
+    if navigations
+        .iter()
+        .any(|n| n.network_id.as_deref() == Some(network_id))
+    {
+        return true;
+    }
+    let url = navigation_url(url);
+    match navigations
+        .iter_mut()
+        .find(|n| n.network_id.is_none() && n.url == url)
+    {
+        Some(navigation) => {
+            navigation.network_id = Some(network_id.to_string());
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns true if a beforeunload dialog belongs to a navigation started by
+/// alcro, which gets the first dialog shown before its request started.
+fn is_own_unload(w: &Window) -> bool {
+    let mut navigations = w.own_navigations.lock().expect("Unable to lock");
+    match navigations
+        .iter_mut()
+        .find(|n| n.unloading && n.network_id.is_none())
+    {
+        Some(navigation) => {
+            navigation.unloading = false;
+            true
+        }
+        None => false,
+    }
+}
+
+async fn navigate(w: &Arc<Window>, url: &str, until: &WaitUntil) -> Result<(), LoadError> {
     let mut load_recv = w.load_recv.lock().await;
+    let _programmatic = ProgrammaticLoad::new(w, url);
     while load_recv.try_recv().is_ok() {}
     let res = send(w, "Page.navigate", &json!({ "url": url }))
         .await
         .map_err(JSError::from)?;
-    let loader_id = res["loaderId"].as_str().map(|s| s.to_string());
-    // Wait for the load event belonging to this navigation: events are only
-    // counted once the navigation itself (matched by loaderId) has committed,
-    // so a stale load event of the previous page cannot end the wait early.
-    let mut navigated = loader_id.is_none();
-    let _ = tokio::time::timeout(std::time::Duration::from_secs(30), async {
-        loop {
-            match load_recv.recv().await {
-                None => break,
-                Some(LoadEvent::Navigated(l)) => {
-                    if Some(&l) == loader_id.as_ref() {
-                        navigated = true;
-                    }
+    if let Some(error) = res["errorText"].as_str().filter(|e| !e.is_empty()) {
+        return Err(LoadError::Navigation(NavigationError {
+            url: url.to_string(),
+            net_error: Some(error.to_string()),
+            status: None,
+        }));
+    }
+    let status = wait_for_load(w, &mut load_recv, res["loaderId"].as_str(), until).await;
+    check_status(url, status)
+}
+
+/// Reload the page of the window and wait for it as `options` say. Inline
+/// HTML is written into a fresh document again.
+pub async fn reload(
+    w: &Arc<Window>,
+    ignore_cache: bool,
+    options: &LoadOptions,
+) -> Result<(), LoadError> {
+    let html = w.inline_html.lock().expect("Unable to lock").clone();
+    if let Some(html) = html {
+        let base_url = w
+            .frames
+            .get(&w.target)
+            .map(|url| url.clone())
+            .unwrap_or_else(|| "about:blank".to_string());
+        // The HTML is written again instead of reloaded, so the cache is bypassed
+        // by disabling it while the document loads its resources. The guard is
+        // in place before the cache is disabled, in case this is cancelled.
+        let _cache_disabled = ignore_cache.then(|| CacheDisabled(Arc::clone(w)));
+        if ignore_cache {
+            send(
+                w,
+                "Network.setCacheDisabled",
+                &json!({ "cacheDisabled": true }),
+            )
+            .await
+            .map_err(JSError::from)?;
+        }
+        return load(w, &Source::Html { html, base_url }, options).await;
+    }
+    tokio::time::timeout(options.timeout, async {
+        let mut load_recv = w.load_recv.lock().await;
+        let url = w.frames.get(&w.target).map(|url| url.clone());
+        let url = url.as_deref().unwrap_or("");
+        let _programmatic = ProgrammaticLoad::new(w, url);
+        while load_recv.try_recv().is_ok() {}
+        send(w, "Page.reload", &json!({ "ignoreCache": ignore_cache }))
+            .await
+            .map_err(JSError::from)?;
+        let status = wait_for_load(w, &mut load_recv, None, &options.wait_until).await;
+        check_status(url, status)
+    })
+    .await
+    .map_err(|_| LoadError::Timeout(options.timeout))?
+}
+
+/// An entry of the session history of a window, see
+/// [`UI::navigation_history()`](crate::UI::navigation_history).
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize)]
+pub struct HistoryEntry {
+    /// The url of the entry
+    pub url: String,
+    /// The title of the page
+    pub title: String,
+}
+
+/// The session history of a window.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize)]
+pub struct NavigationHistory {
+    /// The index of the current page in `entries`
+    pub current_index: usize,
+    /// The pages of the history, oldest first
+    pub entries: Vec<HistoryEntry>,
+}
+
+/// The session history with the devtools ids of its entries.
+async fn history(w: &Arc<Window>) -> Result<(NavigationHistory, Vec<i64>), JSError> {
+    let res = send(w, "Page.getNavigationHistory", &JSObject::Null).await?;
+    let entries = res["entries"].as_array().cloned().unwrap_or_default();
+    let history = NavigationHistory {
+        current_index: res["currentIndex"].as_u64().unwrap_or(0) as usize,
+        entries: entries
+            .iter()
+            .map(|e| HistoryEntry {
+                url: e["url"].as_str().unwrap_or("").to_string(),
+                title: e["title"].as_str().unwrap_or("").to_string(),
+            })
+            .collect(),
+    };
+    let ids = entries
+        .iter()
+        .map(|e| e["id"].as_i64().unwrap_or(0))
+        .collect();
+    Ok((history, ids))
+}
+
+pub async fn navigation_history(w: &Arc<Window>) -> Result<NavigationHistory, JSError> {
+    Ok(history(w).await?.0)
+}
+
+/// Go `offset` entries back (negative) or forward in the session history and
+/// wait for the page as `options` say. Returns false if there is no such entry.
+pub async fn go_history(
+    w: &Arc<Window>,
+    offset: isize,
+    options: &LoadOptions,
+) -> Result<bool, LoadError> {
+    let (history, ids) = history(w).await?;
+    let Some(index) = history.current_index.checked_add_signed(offset) else {
+        return Ok(false);
+    };
+    let (Some(entry), Some(id)) = (history.entries.get(index), ids.get(index)) else {
+        return Ok(false);
+    };
+    tokio::time::timeout(options.timeout, async {
+        let mut load_recv = w.load_recv.lock().await;
+        let _programmatic = ProgrammaticLoad::new(w, &entry.url);
+        while load_recv.try_recv().is_ok() {}
+        send(w, "Page.navigateToHistoryEntry", &json!({ "entryId": id }))
+            .await
+            .map_err(JSError::from)?;
+        let status = wait_for_load(w, &mut load_recv, None, &options.wait_until).await;
+        check_status(&entry.url, status)
+    })
+    .await
+    .map_err(|_| LoadError::Timeout(options.timeout))??;
+    Ok(true)
+}
+
+pub async fn stop_loading(w: &Arc<Window>) -> Result<(), JSError> {
+    send(w, "Page.stopLoading", &JSObject::Null).await?;
+    Ok(())
+}
+
+/// Wait until the navigation with `loader_id`, or the next navigation if
+/// None, reaches `until`, and return the HTTP status of its document. Events
+/// carry the loaderId of their document, so stale events of the previous page
+/// cannot end the wait early.
+async fn wait_for_load(
+    w: &Arc<Window>,
+    load_recv: &mut mpsc::UnboundedReceiver<LoadEvent>,
+    loader_id: Option<&str>,
+    until: &WaitUntil,
+) -> Option<u16> {
+    let mut loader_id = loader_id.map(|l| l.to_string());
+    // The response arrives before the commit that tells a reload's loaderId
+    let mut statuses = std::collections::HashMap::new();
+    let name = match until {
+        WaitUntil::DomContentLoaded => "DOMContentLoaded",
+        WaitUntil::Load => "load",
+        WaitUntil::NetworkIdle => "networkIdle",
+        WaitUntil::Predicate(_) => "",
+    };
+    loop {
+        match load_recv.recv().await {
+            None => return None,
+            Some(LoadEvent::Response(l, status)) => {
+                statuses.insert(l, status);
+            }
+            Some(LoadEvent::WithinDocument) if loader_id.is_none() => return None,
+            Some(LoadEvent::WithinDocument) => {}
+            Some(LoadEvent::Navigated(l)) => {
+                if loader_id.is_none() {
+                    loader_id = Some(l.clone());
                 }
-                Some(LoadEvent::Loaded) => {
-                    if navigated {
-                        break;
-                    }
+                // The predicate is checked from the commit of the new document on
+                if name.is_empty() && loader_id.as_ref() == Some(&l) {
+                    break;
+                }
+            }
+            Some(LoadEvent::Lifecycle(l, event)) => {
+                if event == name && loader_id.as_ref() == Some(&l) {
+                    break;
+                }
+            }
+            Some(LoadEvent::Restored(l)) => {
+                if loader_id.is_none() || loader_id.as_ref() == Some(&l) {
+                    loader_id = Some(l);
+                    break;
                 }
             }
         }
-    })
-    .await;
-    Ok(())
+    }
+    if let WaitUntil::Predicate(predicate) = until {
+        wait_for_predicate(w, predicate).await;
+    }
+    loader_id.and_then(|l| statuses.remove(&l))
+}
+
+/// Evaluate `predicate` every 100ms until it is truthy. Exceptions count as
+/// false, e.g. while the page has not defined what the predicate uses yet.
+async fn wait_for_predicate(w: &Arc<Window>, predicate: &str) {
+    let expr = format!("!!({})", predicate);
+    while eval(w, &expr).await.ok() != Some(JSObject::Bool(true)) {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
 }
 
 pub async fn eval(w: &Arc<Window>, expr: &str) -> JSResult {
@@ -506,7 +2057,67 @@ pub async fn eval(w: &Arc<Window>, expr: &str) -> JSResult {
     .await
 }
 
+pub async fn call_js(w: &Arc<Window>, path: &str, args: JSObject) -> Result<JSObject, CallJSError> {
+    let args = match args {
+        JSObject::Null => json!([]),
+        JSObject::Array(_) => args,
+        _ => return Err(CallJSError::InvalidArguments(args.to_string())),
+    };
+    let expr = format!(
+        r"(async () => {{
+        let thisArg = window, f = window;
+        for (const p of {path}.split('.')) {{
+            thisArg = f;
+            f = f == null ? undefined : f[p];
+        }}
+        if (typeof f !== 'function') return {{ status: 'notAFunction' }};
+        try {{
+            return {{ status: 'ok', value: await f.apply(thisArg, {args}) }};
+        }} catch (e) {{
+            if (e instanceof Error)
+                return {{ status: 'exception', name: e.name, message: e.message, stack: e.stack }};
+            return {{ status: 'thrown', value: e }};
+        }}
+        }})()",
+        path = json!(path),
+        args = args
+    );
+    let res = eval(w, &expr).await.map_err(JSError::from)?;
+    match res["status"].as_str() {
+        Some("ok") => Ok(res["value"].clone()),
+        Some("notAFunction") => Err(CallJSError::NotAFunction(path.to_string())),
+        Some("exception") => Err(CallJSError::Exception {
+            name: res["name"].as_str().unwrap_or("Error").to_string(),
+            message: res["message"].as_str().unwrap_or("").to_string(),
+            stack: res["stack"].as_str().map(|s| s.to_string()),
+        }),
+        _ => Err(CallJSError::Thrown(res["value"].clone())),
+    }
+}
+
+/// Evaluate js code in one execution context, such as the frame that called a
+/// binding.
+pub async fn eval_in_context(
+    w: &Arc<Window>,
+    session: &str,
+    context_id: i64,
+    expr: &str,
+) -> JSResult {
+    send_to(
+        w,
+        session,
+        "Runtime.evaluate",
+        &json!({
+            "expression": expr, "awaitPromise": true, "returnByValue": true, "contextId": context_id
+        }),
+    )
+    .await
+}
+
 pub async fn set_bounds(w: &Arc<Window>, b: Bounds) -> Result<(), JSError> {
+    if w.chrome.headless {
+        return set_viewport(w, b).await;
+    }
     let param = json!({
         "windowId": w.window_id.load(Ordering::Relaxed),
         "bounds": if b.window_state != WindowState::Normal {
@@ -522,7 +2133,217 @@ pub async fn set_bounds(w: &Arc<Window>, b: Bounds) -> Result<(), JSError> {
         .to_result_of_jserror()
 }
 
+/// Headless windows have no window id: their bounds are emulated by
+/// resizing the viewport instead. Only the normal window state exists there.
+async fn set_viewport(w: &Arc<Window>, b: Bounds) -> Result<(), JSError> {
+    if b.window_state != WindowState::Normal {
+        return Err(JSObject::String(format!(
+            "The window state {:?} is not supported in headless mode",
+            b.window_state
+        ))
+        .into());
+    }
+    send(
+        w,
+        "Emulation.setDeviceMetricsOverride",
+        &json!({
+            "width": b.width,
+            "height": b.height,
+            "deviceScaleFactor": 0,
+            "mobile": false,
+            "positionX": b.left.max(0),
+            "positionY": b.top.max(0),
+        }),
+    )
+    .await
+    .map_err(JSError::from)?;
+    *w.viewport.lock().expect("Unable to lock") = Some(b);
+    Ok(())
+}
+
+/// Lists the displays with the Window Management API, or only the window's
+/// current display where the API is unavailable (e.g. on insecure origins).
+const DISPLAYS_SCRIPT: &str = r#"(async () => {
+    const rect = (left, top, width, height) => ({ left, top, width, height });
+    const display = (s, primary) => ({
+        label: s.label || '',
+        bounds: rect(s.left ?? 0, s.top ?? 0, s.width, s.height),
+        workArea: rect(s.availLeft ?? 0, s.availTop ?? 0, s.availWidth, s.availHeight),
+        scaleFactor: s.devicePixelRatio ?? devicePixelRatio,
+        primary,
+    });
+    if (typeof getScreenDetails === 'function') {
+        try {
+            const timeout = new Promise((_, reject) => setTimeout(reject, 2000));
+            const details = await Promise.race([getScreenDetails(), timeout]);
+            return details.screens.map(s => display(s, s.isPrimary));
+        } catch (e) {}
+    }
+    return [display(screen, true)];
+})()"#;
+
+pub async fn displays(w: &Arc<Window>) -> Result<Vec<DisplayInfo>, JSError> {
+    // Allows the app's own pages to list the displays without a permission
+    // prompt. Older browsers call the permission windowPlacement. Other
+    // origins, e.g. remote sites, are not granted the user's screen layout
+    // and fall back to the window's display, as do opaque origins like data:
+    // urls, which cannot be granted anything.
+    let origin = eval(w, "location.origin").await.map_err(JSError::from)?;
+    if let Some(origin) = origin
+        .as_str()
+        .filter(|o| *o == ASSETS_ORIGIN || *o == "file://")
+    {
+        for permission in ["windowManagement", "windowPlacement"] {
+            let res = send_browser(
+                &w.chrome,
+                "Browser.grantPermissions",
+                &json!({ "permissions": [permission], "origin": origin }),
+            )
+            .await;
+            if res.is_ok() {
+                break;
+            }
+        }
+    }
+    let displays = eval(w, DISPLAYS_SCRIPT).await.map_err(JSError::from)?;
+    serde_json::from_value(displays)
+        .map_err(|e| JSObject::String(format!("Invalid displays: {}", e)).into())
+}
+
+/// Bring a minimized window back to the normal state, in place.
+pub async fn restore_window(w: &Arc<Window>) -> Result<(), JSError> {
+    let current = bounds(w).await.map_err(JSError::from)?;
+    if current.window_state != WindowState::Minimized {
+        return Ok(());
+    }
+    // Without bounds, which cannot be combined with leaving the minimized state
+    send(
+        w,
+        "Browser.setWindowBounds",
+        &json!({
+            "windowId": w.window_id.load(Ordering::Relaxed),
+            "bounds": { "windowState": WindowState::Normal },
+        }),
+    )
+    .await
+    .to_result_of_jserror()
+}
+
+/// Whether any part of `w` is visible on a display
+async fn is_on_display(w: &Arc<Window>) -> Result<bool, JSError> {
+    let rect = bounds(w).await.map_err(JSError::from)?.rect();
+    let displays = displays(w).await.unwrap_or_default();
+    Ok(displays.iter().any(|d| d.bounds.overlap(&rect) > 0))
+}
+
+/// Show a window that was launched outside of the displays to stay hidden
+/// while its content loaded, with the requested `width` and `height`: at the
+/// requested `position`, or else in the middle of the primary display.
+pub async fn show_window(
+    w: &Arc<Window>,
+    width: i32,
+    height: i32,
+    position: Option<(i32, i32)>,
+) -> Result<(), JSError> {
+    let requested = Bounds {
+        left: 0,
+        top: 0,
+        width,
+        height,
+        window_state: WindowState::Normal,
+    };
+    let shown = match position {
+        Some((left, top)) => Bounds {
+            left,
+            top,
+            ..requested
+        },
+        None => {
+            let displays = displays(w).await.unwrap_or_default();
+            let primary = displays
+                .iter()
+                .find(|d| d.primary)
+                .or_else(|| displays.first());
+            match primary {
+                Some(display) => requested.center_on(display),
+                None => requested,
+            }
+        }
+    };
+    set_bounds(w, shown).await
+}
+
+pub async fn emulate(w: &Arc<Window>, profile: &DeviceProfile) -> Result<(), JSError> {
+    // The size of a headless window is the viewport set_bounds emulates, which
+    // the profile replaces for as long as it is emulated
+    let viewport = *w.viewport.lock().expect("Unable to lock");
+    let mut profile = profile.clone();
+    if let Some(b) = viewport {
+        if profile.width == 0 {
+            profile.width = b.width;
+        }
+        if profile.height == 0 {
+            profile.height = b.height;
+        }
+    }
+    send(w, "Emulation.setDeviceMetricsOverride", &profile.metrics())
+        .await
+        .map_err(JSError::from)?;
+    send(
+        w,
+        "Emulation.setTouchEmulationEnabled",
+        &json!({ "enabled": profile.touch, "maxTouchPoints": 5 }),
+    )
+    .await
+    .map_err(JSError::from)?;
+    // An empty user agent restores the browser's
+    send(
+        w,
+        "Emulation.setUserAgentOverride",
+        &json!({ "userAgent": profile.user_agent.as_deref().unwrap_or("") }),
+    )
+    .await
+    .to_result_of_jserror()
+}
+
+pub async fn reset_emulation(w: &Arc<Window>) -> Result<(), JSError> {
+    send(w, "Emulation.clearDeviceMetricsOverride", &JSObject::Null)
+        .await
+        .map_err(JSError::from)?;
+    // Back to the bounds set_bounds gave a headless window
+    let viewport = *w.viewport.lock().expect("Unable to lock");
+    if let Some(b) = viewport {
+        set_viewport(w, b).await?;
+    }
+    send(
+        w,
+        "Emulation.setTouchEmulationEnabled",
+        &json!({ "enabled": false }),
+    )
+    .await
+    .map_err(JSError::from)?;
+    send(
+        w,
+        "Emulation.setUserAgentOverride",
+        &json!({ "userAgent": "" }),
+    )
+    .await
+    .to_result_of_jserror()
+}
+
 pub async fn bounds(w: &Arc<Window>) -> Result<Bounds, JSObject> {
+    if w.chrome.headless {
+        if let Some(b) = *w.viewport.lock().expect("Unable to lock") {
+            return Ok(b);
+        }
+        let b = eval(
+            w,
+            "({ left: screenX, top: screenY, width: outerWidth, height: outerHeight, \
+             windowState: 'normal' })",
+        )
+        .await?;
+        return Ok(serde_json::from_value(b).expect("Value not of bounds datatype"));
+    }
     match send(
         w,
         "Browser.getWindowBounds",
@@ -541,32 +2362,332 @@ pub async fn bounds(w: &Arc<Window>) -> Result<Bounds, JSObject> {
     }
 }
 
-pub async fn load_js(w: &Arc<Window>, script: &str) -> Result<(), JSError> {
-    if let Err(e) = send(
+/// Name of the internal binding the window observer script reports through
+pub const WINDOW_EVENT_BINDING: &str = "alcroWindowEvent";
+
+/// Isolated world the window observer runs in, out of reach of page scripts
+const OBSERVER_WORLD: &str = "alcroObserver";
+
+/// Reports coming in quicker than this, e.g. while the window is resized,
+/// are merged into one
+const REPORT_DELAY: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Reports focus, blur, visibility and geometry changes of the top level
+/// document to WINDOW_EVENT_BINDING. Browsers have no event for moving a
+/// window, so the position is polled. Events dispatched by page scripts are
+/// ignored. Running it again replaces the previous observer of the document.
+fn window_observer_script() -> String {
+    format!(
+        r#"(() => {{
+        if (window !== window.top || !window['{name}']) return;
+        const previous = window['alcroWindowObserver'];
+        if (previous) previous.stop();
+        const report = (kind) => window['{name}'](JSON.stringify({{
+            kind,
+            visible: document.visibilityState === 'visible',
+        }}));
+        const onFocus = (e) => e.isTrusted && report('focus');
+        // Focus moving into an iframe blurs the window but not the page
+        const onBlur = (e) => e.isTrusted && setTimeout(() => document.hasFocus() || report('blur'));
+        const onResize = (e) => e.isTrusted && report('bounds');
+        const onVisibility = (e) => e.isTrusted && report('visibility');
+        let x = screenX, y = screenY;
+        const poll = setInterval(() => {{
+            if (screenX !== x || screenY !== y) {{
+                x = screenX;
+                y = screenY;
+                report('bounds');
+            }}
+        }}, 250);
+        addEventListener('focus', onFocus);
+        addEventListener('blur', onBlur);
+        addEventListener('resize', onResize);
+        document.addEventListener('visibilitychange', onVisibility);
+        window['alcroWindowObserver'] = {{ stop: () => {{
+            clearInterval(poll);
+            removeEventListener('focus', onFocus);
+            removeEventListener('blur', onBlur);
+            removeEventListener('resize', onResize);
+            document.removeEventListener('visibilitychange', onVisibility);
+        }} }};
+        report('bounds');
+    }})();"#,
+        name = WINDOW_EVENT_BINDING
+    )
+}
+
+/// Install the window observer for the current and all future documents of
+/// the main frame, in an isolated world so that page scripts can neither call
+/// its binding nor stop it.
+async fn observe_window(w: &Arc<Window>) -> JSResult {
+    send(
+        w,
+        "Runtime.addBinding",
+        &json!({ "name": WINDOW_EVENT_BINDING, "executionContextName": OBSERVER_WORLD }),
+    )
+    .await?;
+    send(
         w,
         "Page.addScriptToEvaluateOnNewDocument",
-        &json!({ "source": script }),
+        &json!({ "source": window_observer_script(), "worldName": OBSERVER_WORLD }),
+    )
+    .await?;
+    watch_window_reports(w);
+    run_observer(w).await
+}
+
+/// Run the window observer on the current document of the main frame.
+async fn run_observer(w: &Arc<Window>) -> JSResult {
+    let context_id = observer_context(w).await?;
+    send(
+        w,
+        "Runtime.evaluate",
+        &json!({
+            "expression": window_observer_script(),
+            "contextId": context_id,
+        }),
     )
     .await
-    {
-        return Err(e.into());
+}
+
+/// The execution context of the observer's world in the current document of
+/// the main frame, which is created if the document has none yet.
+async fn observer_context(w: &Arc<Window>) -> JSResult {
+    let world = send(
+        w,
+        "Page.createIsolatedWorld",
+        &json!({ "frameId": w.target, "worldName": OBSERVER_WORLD }),
+    )
+    .await?;
+    Ok(world["executionContextId"].clone())
+}
+
+/// Whether a binding call came from the window observer, i.e. from its world
+/// in the main frame of the window.
+pub fn is_observer_context(w: &Window, session: &str, context_id: i64) -> bool {
+    session == w.session
+        && w.contexts
+            .get(&(session.to_string(), context_id))
+            .is_some_and(|c| c.frame_id == w.target && c.name == OBSERVER_WORLD)
+}
+
+/// Handle a report of the window observer. Focus changes are sent right
+/// away, the rest is left to the task started by watch_window_reports.
+pub fn window_event(w: &Window, report: &JSObject) {
+    match report["kind"].as_str() {
+        Some("focus") => {
+            let _ = w.window_events.send(WindowEvent::Focused);
+        }
+        Some("blur") => {
+            let _ = w.window_events.send(WindowEvent::Blurred);
+        }
+        _ => {}
     }
-    eval(w, &script).await.to_result_of_jserror()
+    let visible = report["visible"].as_bool().unwrap_or(true);
+    w.window_reports.send_replace(visible);
 }
 
-pub async fn load_css(w: &Arc<Window>, css: &str) -> Result<(), JSError> {
-    let frame_tree = match send(w, "Page.getFrameTree", &json!({ "targetId": w.target })).await {
-        Ok(ft) => ft,
-        Err(e) => return Err(e.into()),
+/// Turn the reports of the window observer into window events, fetching the
+/// bounds once for all reports within REPORT_DELAY. Bounds are only reported
+/// when they changed since the last report, which every new document sends on
+/// start.
+fn watch_window_reports(w: &Arc<Window>) {
+    let mut reports = w.window_reports.subscribe();
+    let w = Arc::downgrade(w);
+    tokio::spawn(async move {
+        let mut observed: Option<ObservedWindow> = None;
+        while reports.changed().await.is_ok() {
+            tokio::time::sleep(REPORT_DELAY).await;
+            let visible = *reports.borrow_and_update();
+            let Some(w) = w.upgrade() else {
+                break;
+            };
+            if w.is_closed() {
+                break;
+            }
+            let Ok(bounds) = bounds(&w).await else {
+                continue;
+            };
+            if let Some(last) = observed.as_ref() {
+                if last.bounds.window_state != bounds.window_state {
+                    let _ = w
+                        .window_events
+                        .send(WindowEvent::StateChanged(bounds.window_state));
+                }
+                if last.bounds != bounds {
+                    let _ = w.window_events.send(WindowEvent::BoundsChanged(bounds));
+                }
+                if last.visible != visible {
+                    let _ = w
+                        .window_events
+                        .send(WindowEvent::VisibilityChanged { visible });
+                }
+            }
+            observed = Some(ObservedWindow { bounds, visible });
+        }
+    });
+}
+
+/// Makes leaving the document ask for confirmation, which the browser turns
+/// into a beforeunload dialog. Running it again replaces the previous hook.
+/// It runs in the observer's world, where page scripts cannot remove it.
+const CLOSE_HOOK_SCRIPT: &str = r#"(() => {
+    const previous = window['alcroCloseHook'];
+    if (previous) removeEventListener('beforeunload', previous);
+    const hook = (e) => {
+        e.preventDefault();
+        e.returnValue = '';
     };
-    let frame_id = frame_tree["frameTree"]["frame"]["id"].as_str().unwrap();
-    let style_sheet = match send(w, "CSS.createStyleSheet", &json!({ "frameId": frame_id })).await {
-        Ok(ss) => ss,
-        Err(e) => return Err(e.into()),
+    window['alcroCloseHook'] = hook;
+    addEventListener('beforeunload', hook);
+})();"#;
+
+pub async fn set_close_handler(w: &Arc<Window>, handler: CloseFunc) -> Result<(), JSError> {
+    *w.close_handler.write().expect("Unable to lock") = Some(handler);
+    arm_close_hook(w).await.to_result_of_jserror()
+}
+
+/// Install the close hook in the current document of the main frame. The
+/// browser only shows beforeunload dialogs for documents the user interacted
+/// with, so the hook is installed as a user gesture.
+async fn arm_close_hook(w: &Arc<Window>) -> JSResult {
+    if w.close_handler.read().expect("Unable to lock").is_none() {
+        return Ok(JSObject::Null);
+    }
+    let context_id = observer_context(w).await?;
+    send(
+        w,
+        "Runtime.evaluate",
+        &json!({
+            "expression": CLOSE_HOOK_SCRIPT,
+            "contextId": context_id,
+            "userGesture": true,
+        }),
+    )
+    .await
+}
+
+/// Re-arm the close hook after the main frame committed a new document.
+pub fn close_hook_navigated(w: &Arc<Window>) {
+    if w.close_handler.read().expect("Unable to lock").is_some() {
+        let w = Arc::clone(w);
+        tokio::spawn(async move {
+            let _ = arm_close_hook(&w).await;
+        });
+    }
+}
+
+/// Answer a beforeunload dialog with the close handler. The dialog of a
+/// navigation started by alcro itself is always accepted, and without a
+/// handler the dialog is left to the browser.
+pub fn close_requested(w: &Arc<Window>) {
+    let handler = w.close_handler.read().expect("Unable to lock").clone();
+    let Some(handler) = handler else {
+        return;
     };
+    let programmatic = is_own_unload(w);
+    let w = Arc::clone(w);
+    tokio::spawn(async move {
+        let accept = programmatic || handler().await;
+        if let Err(e) = send(
+            &w,
+            "Page.handleJavaScriptDialog",
+            &json!({ "accept": accept }),
+        )
+        .await
+        {
+            eprintln!("Unable to answer close request: {}", e);
+        }
+    });
+}
+
+pub async fn load_js(w: &Arc<Window>, script: &str) -> Result<(), JSError> {
+    w.scripts
+        .lock()
+        .expect("Unable to lock")
+        .push(script.to_string());
+    for session in sessions(w) {
+        let res = send_to(
+            w,
+            &session,
+            "Page.addScriptToEvaluateOnNewDocument",
+            &json!({ "source": script }),
+        )
+        .await;
+        if session == w.session {
+            res.to_result_of_jserror()?;
+        }
+    }
+    eval_in_frames(w, script).await
+}
+
+pub async fn load_css(w: &Arc<Window>, css: &str) -> Result<(), JSError> {
+    w.styles
+        .lock()
+        .expect("Unable to lock")
+        .push(css.to_string());
+    add_css(w, css).await
+}
+
+/// Add `css` to every frame of the current documents of the window.
+async fn add_css(w: &Arc<Window>, css: &str) -> Result<(), JSError> {
+    for session in sessions(w) {
+        let frame_tree = match send_to(w, &session, "Page.getFrameTree", &JSObject::Null).await {
+            Ok(ft) => ft,
+            Err(e) if session == w.session => return Err(e.into()),
+            Err(_) => continue,
+        };
+        let mut frames = vec![];
+        collect_frame_ids(&frame_tree["frameTree"], &mut frames);
+        for frame_id in frames {
+            let res = add_style_sheet(w, &session, &frame_id, css).await;
+            // Other frames may be detaching while this runs, only the main
+            // frame is required to succeed.
+            if session == w.session && frame_id == w.target {
+                res?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn collect_frame_ids(tree: &JSObject, frames: &mut Vec<String>) {
+    if let Some(id) = tree["frame"]["id"].as_str() {
+        frames.push(id.to_string());
+    }
+    for child in tree["childFrames"].as_array().into_iter().flatten() {
+        collect_frame_ids(child, frames);
+    }
+}
+
+async fn add_style_sheet(
+    w: &Arc<Window>,
+    session: &str,
+    frame_id: &str,
+    css: &str,
+) -> Result<(), JSError> {
+    let style_sheet = send_to(
+        w,
+        session,
+        "CSS.createStyleSheet",
+        &json!({ "frameId": frame_id }),
+    )
+    .await
+    .map_err(JSError::from)?;
     let style_sheet_id = style_sheet["styleSheetId"].as_str().unwrap();
-    send(
+    set_style_sheet_text(w, session, style_sheet_id, css).await
+}
+
+/// Replace the rules of a stylesheet in place, without reloading it.
+async fn set_style_sheet_text(
+    w: &Arc<Window>,
+    session: &str,
+    style_sheet_id: &str,
+    css: &str,
+) -> Result<(), JSError> {
+    send_to(
         w,
+        session,
         "CSS.setStyleSheetText",
         &json!({ "styleSheetId": style_sheet_id, "text": css }),
     )
@@ -574,17 +2695,60 @@ pub async fn load_css(w: &Arc<Window>, css: &str) -> Result<(), JSError> {
     .to_result_of_jserror()
 }
 
-pub async fn bind(w: &Arc<Window>, name: &str, f: BindingFunc) -> Result<(), JSError> {
-    w.bindings.insert(name.to_string(), f);
+/// The window's own session followed by its child sessions.
+fn sessions(w: &Arc<Window>) -> Vec<String> {
+    let mut sessions = vec![w.session.clone()];
+    sessions.extend(w.children.iter().map(|e| e.key().clone()));
+    sessions
+}
 
-    if let Err(e) = send(w, "Runtime.addBinding", &json!({ "name": name })).await {
-        return Err(e.into());
+/// Evaluate `script` in the main frame, and on a best effort basis in every
+/// other frame of the window (same-process iframes have their own execution
+/// contexts, out-of-process ones live in child sessions).
+async fn eval_in_frames(w: &Arc<Window>, script: &str) -> Result<(), JSError> {
+    // The main frame of a page target shares the target's id.
+    let others: Vec<(String, i64)> = w
+        .contexts
+        .iter()
+        .filter(|e| {
+            e.value().is_default && !(e.key().0 == w.session && e.value().frame_id == w.target)
+        })
+        .map(|e| e.key().clone())
+        .collect();
+    for (session, context_id) in others {
+        let _ = send_to(
+            w,
+            &session,
+            "Runtime.evaluate",
+            &json!({ "expression": script, "contextId": context_id }),
+        )
+        .await;
     }
+    eval(w, script).await.to_result_of_jserror()
+}
 
-    let script = format!(
+/// The JS wrapper of the binding `name`. Frames whose origin the binding's own
+/// policy does not allow get no binding at all. The window's policy can change at
+/// any time, so it is only checked on the rust side, in binding_called.
+fn binding_script(w: &Arc<Window>, name: &str) -> String {
+    let policy = w
+        .bindings
+        .get(name)
+        .map(|b| b.options.origin_policy.to_json())
+        .unwrap_or(JSObject::Null);
+    format!(
         r"(()=>{{
         const bindingName = '{name}';
         const binding = window[bindingName];
+        if (!binding || binding['alcroWrapped']) return;
+        const allows = (list, o) => list === null || list.some(p => p === '*' || p === o ||
+            (p.includes('://*.') && o.startsWith(p.split('://*.')[0] + '://') &&
+             o.endsWith('.' + p.split('://*.')[1]) &&
+             o.length > p.split('://*.')[0].length + p.split('://*.')[1].length + 4));
+        if (!allows({policy}, location.origin)) {{
+            delete window[bindingName];
+            return;
+        }}
         window[bindingName] = async (...args) => {{
             const me = window[bindingName];
             let errors = me['errors'];
@@ -605,25 +2769,116 @@ pub async fn bind(w: &Arc<Window>, name: &str, f: BindingFunc) -> Result<(), JSE
             }});
             binding(JSON.stringify({{name: bindingName, seq, args}}));
             return promise;
-        }}}})();
+        }};
+        window[bindingName]['alcroWrapped'] = true;
+        }})();
    ",
-        name = name
-    );
+        name = name,
+        policy = policy,
+    )
+}
 
-    if let Err(e) = send(
+/// Expose the binding `name` in `session`, for the current and all future
+/// documents. The wrapper script still has to be evaluated in documents that
+/// already exist.
+async fn install_binding(w: &Arc<Window>, session: &str, name: &str) -> Result<(), JSError> {
+    send_to(w, session, "Runtime.addBinding", &json!({ "name": name }))
+        .await
+        .map_err(JSError::from)?;
+    send_to(
         w,
+        session,
         "Page.addScriptToEvaluateOnNewDocument",
-        &json!({ "source": script }),
+        &json!({ "source": binding_script(w, name) }),
     )
     .await
-    {
-        return Err(e.into());
+    .to_result_of_jserror()
+}
+
+pub async fn bind(
+    w: &Arc<Window>,
+    name: &str,
+    f: BindingFunc,
+    options: BindingOptions,
+) -> Result<(), JSError> {
+    let queue = options.max_concurrency.map(BindingQueue::new);
+    w.bindings.insert(
+        name.to_string(),
+        Binding {
+            func: f,
+            options,
+            queue,
+        },
+    );
+
+    for session in sessions(w) {
+        let res = install_binding(w, &session, name).await;
+        if session == w.session {
+            res?;
+        }
+    }
+    eval_in_frames(w, &binding_script(w, name)).await
+}
+
+/// Set up a target that was auto-attached below a window. Iframes get the
+/// window's bindings and scripts before their document starts running.
+pub async fn init_child(w: Arc<Window>, session: String, kind: String) {
+    if kind == "iframe" {
+        for (method, params) in [
+            ("Runtime.enable", JSObject::Null),
+            ("Page.enable", JSObject::Null),
+            // For the bodies of large requests
+            ("Network.enable", JSObject::Null),
+            ("DOM.enable", JSObject::Null),
+            ("CSS.enable", JSObject::Null),
+            (
+                "Target.setAutoAttach",
+                json!({"autoAttach": true, "waitForDebuggerOnStart": true, "flatten": false}),
+            ),
+        ]
+        .iter()
+        {
+            let _ = send_to(&w, &session, method, params).await;
+        }
+        let _ = enable_fetch(&w, &session).await;
+        let names: Vec<String> = w.bindings.iter().map(|e| e.key().clone()).collect();
+        for name in names {
+            let _ = install_binding(&w, &session, &name).await;
+        }
+        let scripts = w.scripts.lock().expect("Unable to lock").clone();
+        for script in scripts {
+            let _ = send_to(
+                &w,
+                &session,
+                "Page.addScriptToEvaluateOnNewDocument",
+                &json!({ "source": script }),
+            )
+            .await;
+        }
     }
-    eval(w, &script).await.to_result_of_jserror()
+    let _ = send_to(
+        &w,
+        &session,
+        "Runtime.runIfWaitingForDebugger",
+        &JSObject::Null,
+    )
+    .await;
 }
 
-/// Close this window. The browser process exits when its last window closes.
+/// Close the browser and all of its windows gracefully.
+pub async fn close_browser(c: &Arc<Chrome>) {
+    if let Err(e) = send_browser(c, "Browser.close", &JSObject::Null).await {
+        // The browser exits before it can answer
+        if !c.closed.load(Ordering::Relaxed) {
+            eprintln!("Unable to close browser: {}", e);
+        }
+    }
+}
+
+/// Close this window. The browser process exits when its last window closes,
+/// unless a Browser handle keeps it alive.
 pub async fn close(w: &Arc<Window>) {
+    w.cancel_start();
     match send_browser(
         &w.chrome,
         "Target.closeTarget",
@@ -639,3 +2894,117 @@ pub async fn close(w: &Arc<Window>) {
         Err(e) => eprintln!("{}", e),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn display(left: i32, width: i32, primary: bool) -> DisplayInfo {
+        let bounds = Rect {
+            left,
+            top: 0,
+            width,
+            height: 1080,
+        };
+        DisplayInfo {
+            label: String::new(),
+            bounds,
+            work_area: Rect {
+                height: 1040,
+                ..bounds
+            },
+            scale_factor: 1.0,
+            primary,
+        }
+    }
+
+    #[test]
+    fn test_navigation_url() {
+        assert_eq!(navigation_url("HTTPS://App.Local"), "https://app.local/");
+        assert_eq!(
+            navigation_url("https://app.local/Page?q=1#top"),
+            "https://app.local/Page?q=1"
+        );
+        assert_eq!(
+            navigation_url("https://app.local?q=1"),
+            "https://app.local/?q=1"
+        );
+        assert_eq!(
+            navigation_url("file:///home/me/index.html"),
+            "file:///home/me/index.html"
+        );
+        assert_eq!(navigation_url("about:blank"), "about:blank");
+    }
+
+    #[test]
+    fn test_clamp_bounds() {
+        let displays = [display(0, 1920, true), display(1920, 1280, false)];
+        let bounds = |left, top, width, height| Bounds {
+            left,
+            top,
+            width,
+            height,
+            window_state: WindowState::Normal,
+        };
+        // Visible bounds stay where they are
+        assert_eq!(
+            bounds(2000, 100, 800, 600).clamp_to(&displays),
+            bounds(2000, 100, 800, 600)
+        );
+        // Partly visible bounds move onto the display they overlap most
+        assert_eq!(
+            bounds(2800, 700, 800, 600).clamp_to(&displays),
+            bounds(2400, 440, 800, 600)
+        );
+        // Bounds on a disconnected display move to the primary display
+        assert_eq!(
+            bounds(-1500, 100, 800, 600).clamp_to(&displays),
+            bounds(0, 100, 800, 600)
+        );
+        // Bounds larger than the work area shrink
+        assert_eq!(
+            bounds(3000, 0, 2000, 1200).clamp_to(&displays),
+            bounds(1920, 0, 1280, 1040)
+        );
+        assert_eq!(
+            bounds(10, 10, 800, 600).clamp_to(&[]),
+            bounds(10, 10, 800, 600)
+        );
+        assert_eq!(
+            bounds(10, 10, 800, 600).center_on(&displays[1]),
+            bounds(2160, 220, 800, 600)
+        );
+    }
+
+    #[test]
+    fn test_device_metrics() {
+        let metrics = DeviceProfile::iphone_se().metrics();
+        assert_eq!(metrics["width"], 375);
+        assert_eq!(metrics["height"], 667);
+        assert_eq!(metrics["deviceScaleFactor"], 2.0);
+        assert_eq!(metrics["mobile"], true);
+
+        let metrics = DeviceProfile::full_hd().zoom(1.5).metrics();
+        assert_eq!(metrics["width"], 1280);
+        assert_eq!(metrics["height"], 720);
+        assert_eq!(metrics["deviceScaleFactor"], 1.5);
+
+        let metrics = DeviceProfile::ipad_mini().rotate().metrics();
+        assert_eq!(metrics["width"], 1024);
+        assert_eq!(metrics["height"], 768);
+    }
+
+    #[test]
+    fn test_origin_policy() {
+        let policy = OriginPolicy::allow_list(&["https://app.local", "https://*.example.com"]);
+        assert!(policy.allows("https://app.local"));
+        assert!(policy.allows("https://a.example.com"));
+        assert!(policy.allows("https://a.b.example.com"));
+        assert!(!policy.allows("https://example.com"));
+        assert!(!policy.allows("https://aexample.com"));
+        assert!(!policy.allows("http://a.example.com"));
+        assert!(!policy.allows("https://app.local:8080"));
+        assert!(!policy.allows("null"));
+        assert!(OriginPolicy::AllowAll.allows("null"));
+        assert!(OriginPolicy::allow_list(&["*"]).allows("https://example.org"));
+    }
+}
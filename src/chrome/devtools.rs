@@ -1,4 +1,9 @@
-use super::{Chrome, JSObject, JSResult, LoadEvent, PipeReader, Window};
+use super::{
+    close_hook_navigated, close_requested, fetch::request_paused, init_child, is_observer_context,
+    popup_opened, resume_page, update_auto_attach, window_event, BindingCall, BlockedCall,
+    CallInfo, ChildSession, Chrome, ExecutionContext, JSObject, JSResult, LoadEvent,
+    NavigationEvent, Pending, PipeReader, StyleSheet, Window, WINDOW_EVENT_BINDING,
+};
 use serde_json::json;
 use std::sync::{atomic::Ordering, Arc};
 use tokio::sync::oneshot;
@@ -25,80 +30,66 @@ pub async fn readloop(c: Arc<Chrome>, mut precv: PipeReader) {
             if let Some((session, window)) = destroyed {
                 c.windows.remove(&session);
                 let _ = window.closed_tx.send(true);
+                if window
+                    .new_window_handler
+                    .read()
+                    .expect("Unable to lock")
+                    .is_some()
+                {
+                    let c = Arc::clone(&c);
+                    tokio::spawn(async move {
+                        let _ = update_auto_attach(&c).await;
+                    });
+                }
                 // Fail this window's in-flight commands: their nested
                 // responses will never arrive now that the target is gone.
                 let stale: Vec<i32> = c
                     .pending
                     .iter()
-                    .filter(|e| e.value().0 == session)
+                    .filter(|e| e.value().window == session)
                     .map(|e| *e.key())
                     .collect();
                 for id in stale {
-                    if let Some((_, (_, reschan))) = c.pending.remove(&id) {
-                        let _ = reschan.send(Err(window_closed_error()));
+                    if let Some((_, pending)) = c.pending.remove(&id) {
+                        let _ = pending.reschan.send(Err(window_closed_error()));
                     }
                 }
             }
             // Prune windows whose handles were dropped, then exit once no
-            // window is left (and none is being created): the browser
-            // process is no longer needed.
+            // window is left (and none is being created) and no Browser
+            // handle keeps the browser alive: the process is no longer needed.
             c.windows.retain(|_, w| w.upgrade().is_some());
-            if c.windows.is_empty() && c.windows_in_creation.load(Ordering::SeqCst) == 0 {
+            if !c.needed() {
                 c.kill_process();
                 break;
             }
+        } else if pmsg["method"] == "Target.attachedToTarget" && pmsg["sessionId"].is_null() {
+            // A new page, held by the browser level auto-attach until it is
+            // let go. Windows opened by a page have its target as opener;
+            // pages without one, like the windows alcro creates itself, are
+            // let go right away.
+            let params = &pmsg["params"];
+            let info = &params["targetInfo"];
+            let opener = info["openerId"].as_str().filter(|o| !o.is_empty());
+            if let (Some(paused), None) = (params["sessionId"].as_str(), opener) {
+                let c = Arc::clone(&c);
+                let paused = paused.to_string();
+                tokio::spawn(async move { resume_page(&c, &paused).await });
+            } else if let (Some(paused), Some(target)) =
+                (params["sessionId"].as_str(), info["targetId"].as_str())
+            {
+                let opener = opener.and_then(|opener| {
+                    c.windows
+                        .iter()
+                        .find_map(|e| e.value().upgrade().filter(|w| w.target == opener))
+                });
+                popup_opened(&c, opener, target.to_string(), paused.to_string());
+            }
         } else if pmsg["method"] == "Target.receivedMessageFromTarget" {
             let params = &pmsg["params"];
             let session = params["sessionId"].as_str().unwrap_or("");
             let window = c.windows.get(session).and_then(|w| w.upgrade());
-
-            let message = params["message"]
-                .as_str()
-                .expect("message should be a string");
-            let res: JSObject = serde_json::from_str(message).expect("Invalid JSON");
-
-            if res["id"] == JSObject::Null && res["method"] == "Page.loadEventFired" {
-                if let Some(window) = window {
-                    let _ = window.load_send.send(LoadEvent::Loaded);
-                }
-            } else if res["id"] == JSObject::Null && res["method"] == "Page.frameNavigated" {
-                let frame = &res["params"]["frame"];
-                if frame["parentId"] == JSObject::Null {
-                    if let Some(window) = window {
-                        let _ = window.load_send.send(LoadEvent::Navigated(
-                            frame["loaderId"].as_str().unwrap_or("").to_string(),
-                        ));
-                    }
-                }
-            } else if res["id"] == JSObject::Null && res["method"] == "Runtime.consoleAPICalled"
-                || res["method"] == "Runtime.exceptionThrown"
-            {
-                c.log(&res);
-            } else if res["id"] == JSObject::Null && res["method"] == "Runtime.bindingCalled" {
-                let payload: JSObject = serde_json::from_str(
-                    res["params"]["payload"]
-                        .as_str()
-                        .expect("payload should be a string"),
-                )
-                .expect("Invalid JSON");
-                if let Some(window) = window {
-                    binding_called(
-                        window,
-                        res["params"]["name"].as_str().expect("Expected string"),
-                        payload,
-                        res["params"]["executionContextId"]
-                            .as_i64()
-                            .expect("Expected i64"),
-                    );
-                }
-                continue;
-            } else if res["id"].is_i64() {
-                let res_id = res["id"].as_i64().expect("Expected i64") as i32;
-
-                if let Some((_, (_, reschan))) = c.pending.remove(&res_id) {
-                    send_result(reschan, &res);
-                }
-            }
+            handle_session_message(&c, window.as_ref(), session, &params["message"]);
         } else if pmsg["id"].is_i64() {
             // Top level messages are responses to browser level commands, or
             // acknowledgements of Target.sendMessageToTarget (whose ids live
@@ -108,8 +99,8 @@ pub async fn readloop(c: Arc<Chrome>, mut precv: PipeReader) {
             if let Some((_, reschan)) = c.pending_browser.remove(&res_id) {
                 send_result(reschan, &pmsg);
             } else if pmsg["error"] != JSObject::Null {
-                if let Some((_, (_, reschan))) = c.pending.remove(&res_id) {
-                    let _ = reschan.send(Err(pmsg["error"]["message"].clone()));
+                if let Some((_, pending)) = c.pending.remove(&res_id) {
+                    let _ = pending.reschan.send(Err(pmsg["error"]["message"].clone()));
                 }
             }
         }
@@ -125,9 +116,262 @@ pub async fn readloop(c: Arc<Chrome>, mut precv: PipeReader) {
         }
     }
     c.windows.clear();
+    c.exited.send_replace(true);
+}
+
+/// Handle a message from a window's session, or from one of its child
+/// sessions (whose messages arrive wrapped in further
+/// Target.receivedMessageFromTarget events of their parent).
+fn handle_session_message(
+    c: &Arc<Chrome>,
+    window: Option<&Arc<Window>>,
+    session: &str,
+    message: &JSObject,
+) {
+    let message = message.as_str().expect("message should be a string");
+    let res: JSObject = serde_json::from_str(message).expect("Invalid JSON");
+    let is_child = window.is_some_and(|w| w.session != session);
+
+    if res["id"] == JSObject::Null && res["method"] == "Target.receivedMessageFromTarget" {
+        let params = &res["params"];
+        let child = params["sessionId"].as_str().unwrap_or("");
+        handle_session_message(c, window, child, &params["message"]);
+    } else if res["id"] == JSObject::Null && res["method"] == "Target.attachedToTarget" {
+        let params = &res["params"];
+        let child = params["sessionId"]
+            .as_str()
+            .expect("Value not of string datatype")
+            .to_string();
+        if let Some(window) = window {
+            window.children.insert(
+                child.clone(),
+                ChildSession {
+                    parent: session.to_string(),
+                },
+            );
+            tokio::spawn(init_child(
+                Arc::clone(window),
+                child,
+                params["targetInfo"]["type"]
+                    .as_str()
+                    .unwrap_or("")
+                    .to_string(),
+            ));
+        }
+    } else if res["id"] == JSObject::Null && res["method"] == "Target.detachedFromTarget" {
+        if let Some(window) = window {
+            let child = res["params"]["sessionId"].as_str().unwrap_or("");
+            window.children.remove(child);
+            window.contexts.retain(|(s, _), _| s != child);
+            window.style_sheets.retain(|(s, _), _| s != child);
+        }
+    } else if res["id"] == JSObject::Null && res["method"] == "Runtime.executionContextCreated" {
+        let context = &res["params"]["context"];
+        if let (Some(window), Some(id)) = (window, context["id"].as_i64()) {
+            window.contexts.insert(
+                (session.to_string(), id),
+                ExecutionContext {
+                    frame_id: context["auxData"]["frameId"]
+                        .as_str()
+                        .unwrap_or("")
+                        .to_string(),
+                    origin: context["origin"].as_str().unwrap_or("").to_string(),
+                    name: context["name"].as_str().unwrap_or("").to_string(),
+                    is_default: context["auxData"]["isDefault"] == true,
+                },
+            );
+        }
+    } else if res["id"] == JSObject::Null && res["method"] == "Runtime.executionContextDestroyed" {
+        if let (Some(window), Some(id)) = (window, res["params"]["executionContextId"].as_i64()) {
+            window.contexts.remove(&(session.to_string(), id));
+        }
+    } else if res["id"] == JSObject::Null && res["method"] == "Runtime.executionContextsCleared" {
+        if let Some(window) = window {
+            window.contexts.retain(|(s, _), _| s != session);
+        }
+    } else if res["id"] == JSObject::Null && res["method"] == "CSS.styleSheetAdded" {
+        let header = &res["params"]["header"];
+        if let (Some(window), Some(id), Some(url)) = (
+            window,
+            header["styleSheetId"].as_str(),
+            header["sourceURL"].as_str().filter(|url| !url.is_empty()),
+        ) {
+            window.style_sheets.insert(
+                (session.to_string(), id.to_string()),
+                StyleSheet {
+                    frame_id: header["frameId"].as_str().unwrap_or("").to_string(),
+                    source_url: url.to_string(),
+                },
+            );
+        }
+    } else if res["id"] == JSObject::Null && res["method"] == "CSS.styleSheetRemoved" {
+        if let (Some(window), Some(id)) = (window, res["params"]["styleSheetId"].as_str()) {
+            window
+                .style_sheets
+                .remove(&(session.to_string(), id.to_string()));
+        }
+    } else if res["id"] == JSObject::Null && res["method"] == "Fetch.requestPaused" {
+        if let Some(window) = window {
+            request_paused(Arc::clone(window), session.to_string(), &res["params"]);
+        }
+    } else if res["id"] == JSObject::Null && res["method"] == "Page.lifecycleEvent" {
+        let params = &res["params"];
+        if let Some(window) = main_frame(window, is_child, params) {
+            let name = params["name"].as_str().unwrap_or("");
+            match name {
+                "DOMContentLoaded" => window.notify(NavigationEvent::DomContentLoaded),
+                "load" => window.notify(NavigationEvent::Loaded),
+                _ => {}
+            }
+            let _ = window.load_send.send(LoadEvent::Lifecycle(
+                params["loaderId"].as_str().unwrap_or("").to_string(),
+                name.to_string(),
+            ));
+        }
+    } else if res["id"] == JSObject::Null && res["method"] == "Network.responseReceived" {
+        let params = &res["params"];
+        if let (Some(window), Some(status)) = (
+            main_frame(window, is_child, params).filter(|_| params["type"] == "Document"),
+            params["response"]["status"].as_u64(),
+        ) {
+            let _ = window.load_send.send(LoadEvent::Response(
+                params["loaderId"].as_str().unwrap_or("").to_string(),
+                status as u16,
+            ));
+        }
+    } else if res["id"] == JSObject::Null && res["method"] == "Page.frameStartedLoading" {
+        if let Some(window) = main_frame(window, is_child, &res["params"]) {
+            window.notify(NavigationEvent::Started);
+        }
+    } else if res["id"] == JSObject::Null && res["method"] == "Page.frameStoppedLoading" {
+        if let Some(window) = main_frame(window, is_child, &res["params"]) {
+            window.notify(NavigationEvent::Finished);
+        }
+    } else if res["id"] == JSObject::Null && res["method"] == "Page.navigatedWithinDocument" {
+        let params = &res["params"];
+        if let (Some(window), Some(frame_id), Some(url)) =
+            (window, params["frameId"].as_str(), params["url"].as_str())
+        {
+            window.frames.insert(frame_id.to_string(), url.to_string());
+        }
+        if let (Some(window), Some(url)) =
+            (main_frame(window, is_child, params), params["url"].as_str())
+        {
+            window.notify(NavigationEvent::WithinDocument {
+                url: url.to_string(),
+            });
+            let _ = window.load_send.send(LoadEvent::WithinDocument);
+        }
+    } else if res["id"] == JSObject::Null && res["method"] == "Page.frameDetached" {
+        if let (Some(window), Some(frame_id)) = (window, res["params"]["frameId"].as_str()) {
+            window.frames.remove(frame_id);
+        }
+    } else if res["id"] == JSObject::Null && res["method"] == "Page.frameNavigated" {
+        let frame = &res["params"]["frame"];
+        if let (Some(window), Some(frame_id), Some(url)) =
+            (window, frame["id"].as_str(), frame["url"].as_str())
+        {
+            window.frames.insert(frame_id.to_string(), url.to_string());
+            // The stylesheets of the previous document are gone
+            window
+                .style_sheets
+                .retain(|(s, _), sheet| s != session || sheet.frame_id != frame_id);
+        }
+        if frame["parentId"] == JSObject::Null {
+            if let Some(window) = window.filter(|_| !is_child) {
+                *window.inline_html.lock().expect("Unable to lock") = None;
+                let loader_id = frame["loaderId"].as_str().unwrap_or("").to_string();
+                let restored = res["params"]["type"] == "BackForwardCacheRestore";
+                let _ = window.load_send.send(if restored {
+                    LoadEvent::Restored(loader_id)
+                } else {
+                    LoadEvent::Navigated(loader_id)
+                });
+                window.notify(NavigationEvent::Committed {
+                    url: frame["url"].as_str().unwrap_or("").to_string(),
+                });
+                if restored {
+                    window.notify(NavigationEvent::Loaded);
+                }
+                close_hook_navigated(window);
+            }
+        }
+    } else if res["id"] == JSObject::Null
+        && res["method"] == "Page.javascriptDialogOpening"
+        && res["params"]["type"] == "beforeunload"
+    {
+        if let Some(window) = window.filter(|_| !is_child) {
+            close_requested(window);
+        }
+    } else if res["id"] == JSObject::Null && res["method"] == "Runtime.consoleAPICalled"
+        || res["method"] == "Runtime.exceptionThrown"
+    {
+        match window {
+            Some(window) => window.log(&res),
+            None => c.log(&res),
+        }
+    } else if res["id"] == JSObject::Null && res["method"] == "Runtime.bindingCalled" {
+        // Pages can call the raw binding with anything, drop what the
+        // wrappers would never send
+        let params = &res["params"];
+        let payload = params["payload"]
+            .as_str()
+            .and_then(|p| serde_json::from_str::<JSObject>(p).ok());
+        if let (Some(window), Some(name), Some(context_id), Some(payload)) = (
+            window,
+            params["name"].as_str(),
+            params["executionContextId"].as_i64(),
+            payload,
+        ) {
+            if name == WINDOW_EVENT_BINDING {
+                if is_observer_context(window, session, context_id) {
+                    window_event(window, &payload);
+                }
+            } else if payload["name"] == name && payload["seq"].is_i64() {
+                binding_called(
+                    Arc::clone(window),
+                    session.to_string(),
+                    name,
+                    payload,
+                    context_id,
+                );
+            }
+        }
+    } else if res["id"].is_i64() {
+        // Commands for child sessions are relayed with the same id through
+        // every session in between, so only the addressed session's response
+        // resolves them; an error from a relaying session fails them.
+        let res_id = res["id"].as_i64().expect("Expected i64") as i32;
+        let addressed = c.pending.get(&res_id).is_some_and(|p| p.session == session);
+        if addressed {
+            if let Some((_, pending)) = c.pending.remove(&res_id) {
+                send_result(pending.reschan, &res);
+            }
+        } else if res["error"] != JSObject::Null {
+            if let Some((_, pending)) = c.pending.remove(&res_id) {
+                let _ = pending.reschan.send(Err(res["error"]["message"].clone()));
+            }
+        }
+    }
+}
+
+/// The window, if an event with `params["frameId"]` is about its main frame.
+/// Child sessions belong to out-of-process iframes.
+fn main_frame<'a>(
+    window: Option<&'a Arc<Window>>,
+    is_child: bool,
+    params: &JSObject,
+) -> Option<&'a Arc<Window>> {
+    window.filter(|w| !is_child && params["frameId"] == w.target.as_str())
 }
 
 pub async fn send(w: &Arc<Window>, method: &str, params: &JSObject) -> JSResult {
+    send_to(w, &w.session, method, params).await
+}
+
+/// Send a command to `session`, which is either the window's own session or
+/// one of its child sessions.
+pub async fn send_to(w: &Arc<Window>, session: &str, method: &str, params: &JSObject) -> JSResult {
     let c = &w.chrome;
     if c.closed.load(Ordering::Relaxed) {
         return Err(browser_closed_error());
@@ -136,25 +380,43 @@ pub async fn send(w: &Arc<Window>, method: &str, params: &JSObject) -> JSResult
         return Err(window_closed_error());
     }
     let id = c.id.fetch_add(1, Ordering::Relaxed) + 1;
-    let json_msg = json!({
+    let mut message = json!({
         "id":id,
         "method":method,
         "params":params
     });
-    let (s, r) = oneshot::channel();
-    c.pending.insert(id, (w.session.clone(), s));
+    // Wrap the command once per session on the way from the browser down to
+    // the addressed session.
+    let mut hop = session.to_string();
+    loop {
+        message = json!({
+            "id":id,
+            "method":"Target.sendMessageToTarget",
+            "params":json!({
+                "message":message.to_string(),
+                "sessionId":hop
+            })
+        });
+        if hop == w.session {
+            break;
+        }
+        hop = match w.children.get(&hop) {
+            Some(child) => child.parent.clone(),
+            None => return Err(frame_detached_error()),
+        };
+    }
 
-    let message = json!({
-        "id":id,
-        "method":"Target.sendMessageToTarget",
-        "params":json!({
-            "message":json_msg.to_string(),
-            "sessionId":w.session
-        })
-    })
-    .to_string();
+    let (s, r) = oneshot::channel();
+    c.pending.insert(
+        id,
+        Pending {
+            window: w.session.clone(),
+            session: session.to_string(),
+            reschan: s,
+        },
+    );
 
-    if let Err(e) = c.psend.lock().await.write(message).await {
+    if let Err(e) = c.psend.lock().await.write(message.to_string()).await {
         c.pending.remove(&id);
         return Err(JSObject::String(format!("Unable to write to pipe: {}", e)));
     }
@@ -166,6 +428,26 @@ pub async fn send(w: &Arc<Window>, method: &str, params: &JSObject) -> JSResult
 }
 
 pub async fn send_browser(c: &Arc<Chrome>, method: &str, params: &JSObject) -> JSResult {
+    send_top_level(c, None, method, params).await
+}
+
+/// Send a command to a session attached in flat mode, whose messages are
+/// tagged with its id on the browser connection instead of being wrapped.
+pub async fn send_flat(
+    c: &Arc<Chrome>,
+    session: &str,
+    method: &str,
+    params: &JSObject,
+) -> JSResult {
+    send_top_level(c, Some(session), method, params).await
+}
+
+async fn send_top_level(
+    c: &Arc<Chrome>,
+    session: Option<&str>,
+    method: &str,
+    params: &JSObject,
+) -> JSResult {
     if c.closed.load(Ordering::Relaxed) {
         return Err(browser_closed_error());
     }
@@ -173,14 +455,16 @@ pub async fn send_browser(c: &Arc<Chrome>, method: &str, params: &JSObject) -> J
     let (s, r) = oneshot::channel();
     c.pending_browser.insert(id, s);
 
-    let message = json!({
+    let mut message = json!({
         "id":id,
         "method":method,
         "params":params
-    })
-    .to_string();
+    });
+    if let Some(session) = session {
+        message["sessionId"] = session.into();
+    }
 
-    if let Err(e) = c.psend.lock().await.write(message).await {
+    if let Err(e) = c.psend.lock().await.write(message.to_string()).await {
         c.pending_browser.remove(&id);
         return Err(JSObject::String(format!("Unable to write to pipe: {}", e)));
     }
@@ -199,6 +483,10 @@ fn window_closed_error() -> JSObject {
     JSObject::String("Window has been closed".to_string())
 }
 
+fn frame_detached_error() -> JSObject {
+    JSObject::String("Frame has been detached".to_string())
+}
+
 fn send_result(reschan: oneshot::Sender<JSResult>, res: &JSObject) {
     let result = if res["error"]["message"] != JSObject::Null {
         Err(res["error"]["message"].clone())
@@ -216,22 +504,92 @@ fn send_result(reschan: oneshot::Sender<JSResult>, res: &JSObject) {
     let _ = reschan.send(result);
 }
 
-fn binding_called(w: Arc<Window>, name: &str, payload: JSObject, context_id: i64) {
-    let binding = w.bindings.get(name).map(|b| Arc::clone(&*b));
-    if let Some(binding) = binding {
+fn binding_called(w: Arc<Window>, session: String, name: &str, payload: JSObject, context_id: i64) {
+    let binding = w
+        .bindings
+        .get(name)
+        .map(|b| (Arc::clone(&b.func), b.options.clone(), b.queue.clone()));
+    if let Some((func, options, queue)) = binding {
         let args = payload["args"].as_array().cloned().unwrap_or_default();
-        // The future is created inline (cheap for an async fn: no user code
-        // runs until it is polled) and then driven on its own task so that
-        // bindings never block the message loop.
-        let fut = binding(args);
-        tokio::spawn(async move {
-            let result = fut.await;
-            complete_binding(w, payload, context_id, result).await;
+        let (origin, url) = match w.contexts.get(&(session.clone(), context_id)) {
+            Some(context) => (
+                context.origin.clone(),
+                w.frames
+                    .get(&context.frame_id)
+                    .map(|u| u.clone())
+                    .unwrap_or_default(),
+            ),
+            None => Default::default(),
+        };
+        if !options.origin_policy.allows(&origin) || !w.origin_policy().allows(&origin) {
+            let blocked = BlockedCall {
+                name: name.to_string(),
+                origin,
+                url,
+            };
+            w.log(&json!({ "blockedCall": blocked }));
+            let error = JSObject::String(format!(
+                "Origin {} is not allowed to call {}",
+                blocked.origin, blocked.name
+            ));
+            let _ = w.blocked_calls.send(blocked);
+            tokio::spawn(complete_binding(
+                w,
+                session,
+                payload,
+                context_id,
+                Err(error),
+            ));
+            return;
+        }
+        let info = CallInfo {
+            window: Arc::clone(&w),
+            session: session.clone(),
+            context_id,
+            origin,
+            url,
+        };
+        // The binding function is only called once the call is admitted: when
+        // the call is first polled on its own task, so that bindings never block
+        // the message loop and rejected calls never reach user code.
+        let rejected = (
+            session.clone(),
+            json!({ "name": name, "seq": payload["seq"] }),
+        );
+        let window = Arc::clone(&w);
+        let call: BindingCall = Box::pin(async move {
+            let result = func(info, args).await;
+            complete_binding(window, session, payload, context_id, result).await;
         });
+        match queue {
+            None => {
+                tokio::spawn(call);
+            }
+            Some(queue) => {
+                if !queue.push(call, options.max_queue) {
+                    // The call was dropped unstarted, reject it in its place.
+                    let (session, payload) = rejected;
+                    let error = JSObject::String(format!("Too many pending calls to {}", name));
+                    tokio::spawn(complete_binding(
+                        w,
+                        session,
+                        payload,
+                        context_id,
+                        Err(error),
+                    ));
+                }
+            }
+        }
     }
 }
 
-async fn complete_binding(w: Arc<Window>, payload: JSObject, context_id: i64, result: JSResult) {
+async fn complete_binding(
+    w: Arc<Window>,
+    session: String,
+    payload: JSObject,
+    context_id: i64,
+    result: JSResult,
+) {
     let (r, e) = match result {
         Ok(x) => (x.to_string(), r#""""#.to_string()),
         Err(e) => ("".to_string(), e.to_string()),
@@ -253,8 +611,9 @@ async fn complete_binding(w: Arc<Window>, payload: JSObject, context_id: i64, re
         error = e
     );
 
-    if let Err(e) = send(
+    if let Err(e) = send_to(
         &w,
+        &session,
         "Runtime.evaluate",
         &json!({
             "expression":expr,
@@ -0,0 +1,588 @@
+use super::{is_own_navigation, os::open_external, send_to, JSObject, Window};
+use serde_json::json;
+use std::{borrow::Cow, sync::Arc};
+
+/// The origin [`Content::Assets`](crate::Content::Assets) are served from. The
+/// `.invalid` domain never resolves, so nothing leaves the browser even if a
+/// request slips past the interception.
+pub const ASSETS_ORIGIN: &str = "https://alcro.invalid";
+
+/// A file served for [`Content::Assets`](crate::Content::Assets).
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Asset {
+    /// Contents of the file
+    pub data: Cow<'static, [u8]>,
+    /// MIME type of the file, e.g. `text/html`
+    pub mime_type: String,
+}
+
+/// Provides the files served for [`Content::Assets`](crate::Content::Assets),
+/// e.g. files embedded in the binary.
+pub trait AssetProvider: Send + Sync {
+    /// Returns the file at `path`, or None if there is no such file. The path is
+    /// relative to the root and has no leading `/`, e.g. `css/style.css`. The root
+    /// itself is requested as `index.html`.
+    fn get(&self, path: &str) -> Option<Asset>;
+}
+
+/// An HTTP request made by the browser, handed to a [`RequestHandler`].
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Request {
+    /// The method, e.g. `GET`
+    pub method: String,
+    /// The full url, e.g. `https://app.local/api/items?page=2`
+    pub url: String,
+    /// The request headers
+    pub headers: Vec<(String, String)>,
+    /// The request body, if any
+    pub body: Option<Vec<u8>>,
+}
+
+/// The response to a [`Request`].
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Response {
+    /// The HTTP status code
+    pub status: u16,
+    /// The response headers
+    pub headers: Vec<(String, String)>,
+    /// The response body
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// A response with the given status and body and no headers
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Response {
+            status,
+            headers: vec![],
+            body: body.into(),
+        }
+    }
+
+    /// Add a header
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+pub type ResponseFuture = std::pin::Pin<Box<dyn std::future::Future<Output = Response> + Send>>;
+
+/// Answers the browser's requests to an origin, see
+/// [`UI::handle_origin()`](crate::UI::handle_origin).
+///
+/// It is implemented for async functions and closures taking a [`Request`] and
+/// returning a [`Response`].
+pub trait RequestHandler: Send + Sync + 'static {
+    /// Returns the response to `request`
+    fn handle(&self, request: Request) -> ResponseFuture;
+}
+
+impl<F, Fut> RequestHandler for F
+where
+    F: Fn(Request) -> Fut + Send + Sync + 'static,
+    Fut: std::future::Future<Output = Response> + Send + 'static,
+{
+    fn handle(&self, request: Request) -> ResponseFuture {
+        Box::pin(self(request))
+    }
+}
+
+/// What to do with an intercepted request, see [`UI::intercept()`](crate::UI::intercept).
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum InterceptAction {
+    /// Let the request proceed unchanged
+    Continue,
+    /// Send the given request instead. The page does not see the change of the url.
+    Modify(Request),
+    /// Answer the request without sending it
+    Fulfill(Response),
+    /// Fail the request as if it had a network error
+    Fail(FailReason),
+}
+
+/// The network error an intercepted request fails with.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, serde::Serialize)]
+pub enum FailReason {
+    Failed,
+    Aborted,
+    TimedOut,
+    AccessDenied,
+    ConnectionClosed,
+    ConnectionReset,
+    ConnectionRefused,
+    ConnectionAborted,
+    ConnectionFailed,
+    NameNotResolved,
+    InternetDisconnected,
+    AddressUnreachable,
+    BlockedByClient,
+    BlockedByResponse,
+}
+
+/// What to do when the page navigates, see
+/// [`UI::set_navigation_policy()`](crate::UI::set_navigation_policy).
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum NavigationAction {
+    /// Navigate in the window
+    Allow,
+    /// Stay on the current page
+    Cancel,
+    /// Stay on the current page and open the url in the user's default browser
+    OpenExternal,
+}
+
+pub type PolicyFuture =
+    std::pin::Pin<Box<dyn std::future::Future<Output = NavigationAction> + Send>>;
+pub type PolicyFunc = Arc<dyn Fn(String) -> PolicyFuture + Sync + Send>;
+
+pub type RouteFuture = std::pin::Pin<Box<dyn std::future::Future<Output = InterceptAction> + Send>>;
+pub type RouteFunc = Arc<dyn Fn(Request) -> RouteFuture + Sync + Send>;
+
+/// Requests matching `pattern` (a Fetch urlPattern, with `*` and `?`
+/// wildcards) are handed to `func`.
+#[derive(Clone)]
+pub struct Route {
+    /// Identifies the route, so it can be replaced or removed
+    pub key: String,
+    pub pattern: String,
+    pub func: RouteFunc,
+    /// Whether windows opened from this window get the route as well
+    pub inherit: bool,
+}
+
+/// Add a route to the window, replacing the route with the same key, and
+/// update the interception patterns of all its sessions.
+pub async fn set_route(w: &Arc<Window>, route: Route) -> Result<(), JSObject> {
+    {
+        let mut routes = w.routes.write().expect("Unable to lock");
+        match routes.iter_mut().find(|r| r.key == route.key) {
+            Some(r) => *r = route,
+            None => routes.push(route),
+        }
+    }
+    update_patterns(w).await
+}
+
+/// Remove the route with the given key, if any, from the window.
+pub async fn remove_route(w: &Arc<Window>, key: &str) -> Result<(), JSObject> {
+    w.routes
+        .write()
+        .expect("Unable to lock")
+        .retain(|r| r.key != key);
+    update_patterns(w).await
+}
+
+/// Set or clear the navigation policy of the window.
+pub async fn set_navigation_policy(
+    w: &Arc<Window>,
+    policy: Option<PolicyFunc>,
+) -> Result<(), JSObject> {
+    *w.navigation_policy.write().expect("Unable to lock") = policy;
+    update_patterns(w).await
+}
+
+/// Whether the window needs Fetch interception at all.
+pub fn intercepts(w: &Window) -> bool {
+    !w.routes.read().expect("Unable to lock").is_empty()
+        || w.navigation_policy
+            .read()
+            .expect("Unable to lock")
+            .is_some()
+}
+
+async fn update_patterns(w: &Arc<Window>) -> Result<(), JSObject> {
+    for session in super::sessions(w) {
+        let res = enable_fetch(w, &session).await;
+        if session == w.session {
+            res?;
+        }
+    }
+    Ok(())
+}
+
+/// Send the window's interception patterns to one of its sessions. Fetch.enable
+/// replaces the previous patterns.
+pub async fn enable_fetch(w: &Arc<Window>, session: &str) -> Result<(), JSObject> {
+    let mut patterns: Vec<JSObject> = w
+        .routes
+        .read()
+        .expect("Unable to lock")
+        .iter()
+        .map(|r| json!({ "urlPattern": r.pattern, "requestStage": "Request" }))
+        .collect();
+    // Navigations of the main frame, for the navigation policy
+    if session == w.session
+        && w.navigation_policy
+            .read()
+            .expect("Unable to lock")
+            .is_some()
+    {
+        patterns.push(
+            json!({ "urlPattern": "*", "resourceType": "Document", "requestStage": "Request" }),
+        );
+    }
+    if patterns.is_empty() {
+        send_to(w, session, "Fetch.disable", &JSObject::Null).await?;
+    } else {
+        send_to(w, session, "Fetch.enable", &json!({ "patterns": patterns })).await?;
+    }
+    Ok(())
+}
+
+/// Handle Fetch.requestPaused, on its own task: navigations of the main frame
+/// that the window did not start itself go by the navigation policy first,
+/// then the first route matching the url decides what happens to the request.
+pub fn request_paused(w: Arc<Window>, session: String, params: &JSObject) {
+    let request_id = params["requestId"].as_str().unwrap_or("").to_string();
+    let request = &params["request"];
+    let url = request["url"].as_str().unwrap_or("").to_string();
+    let policy = w
+        .navigation_policy
+        .read()
+        .expect("Unable to lock")
+        .clone()
+        .filter(|_| {
+            session == w.session
+                && params["frameId"] == w.target.as_str()
+                && params["resourceType"] == "Document"
+                && !is_own_navigation(&w, &url, params["networkId"].as_str().unwrap_or(""))
+        });
+    let method = request["method"].as_str().unwrap_or("GET").to_string();
+    let route = w
+        .routes
+        .read()
+        .expect("Unable to lock")
+        .iter()
+        .find(|r| url_matches(&r.pattern, &url))
+        .cloned();
+    // Large bodies are not sent along with the request
+    let missing_body = request["hasPostData"] == true
+        && request["postData"].is_null()
+        && request["postDataEntries"].is_null();
+    let network_id = params["networkId"].as_str().unwrap_or("").to_string();
+    let mut request = Request {
+        method,
+        url,
+        headers: request["headers"]
+            .as_object()
+            .map(|h| {
+                h.iter()
+                    .map(|(k, v)| (k.clone(), v.as_str().unwrap_or("").to_string()))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        body: post_data(request),
+    };
+    tokio::spawn(async move {
+        if missing_body {
+            request.body = request_body(&w, &session, &network_id).await;
+        }
+        let decision = match policy {
+            Some(policy) => policy(request.url.clone()).await,
+            None => NavigationAction::Allow,
+        };
+        if decision == NavigationAction::OpenExternal {
+            if let Err(e) = open_external(&request.url) {
+                eprintln!("Unable to open {}: {}", request.url, e);
+            }
+        }
+        let action = match (decision, route) {
+            (NavigationAction::Cancel | NavigationAction::OpenExternal, _) => {
+                InterceptAction::Fail(FailReason::Aborted)
+            }
+            (NavigationAction::Allow, Some(route)) => (route.func)(request).await,
+            (NavigationAction::Allow, None) => InterceptAction::Continue,
+        };
+        let header_entries = |headers: &[(String, String)]| {
+            headers
+                .iter()
+                .map(|(name, value)| json!({ "name": name, "value": value }))
+                .collect::<Vec<_>>()
+        };
+        let (method, mut params) = match action {
+            InterceptAction::Continue => ("Fetch.continueRequest", json!({})),
+            InterceptAction::Modify(request) => {
+                let mut params = json!({
+                    "url": request.url,
+                    "method": request.method,
+                    "headers": header_entries(&request.headers),
+                });
+                if let Some(body) = request.body {
+                    params["postData"] = json!(base64_encode(&body));
+                }
+                ("Fetch.continueRequest", params)
+            }
+            InterceptAction::Fulfill(response) => (
+                "Fetch.fulfillRequest",
+                json!({
+                    "responseCode": response.status,
+                    "responseHeaders": header_entries(&response.headers),
+                    "body": base64_encode(&response.body),
+                }),
+            ),
+            InterceptAction::Fail(reason) => {
+                ("Fetch.failRequest", json!({ "errorReason": reason }))
+            }
+        };
+        params["requestId"] = json!(request_id);
+        if let Err(e) = send_to(&w, &session, method, &params).await {
+            eprintln!("{}", e);
+        }
+    });
+}
+
+/// The body of a request that was too large to be sent with Fetch.requestPaused
+async fn request_body(w: &Arc<Window>, session: &str, network_id: &str) -> Option<Vec<u8>> {
+    let res = send_to(
+        w,
+        session,
+        "Network.getRequestPostData",
+        &json!({ "requestId": network_id }),
+    )
+    .await
+    .ok()?;
+    let data = res["postData"].as_str()?;
+    if res["base64Encoded"] == true {
+        base64_decode(data)
+    } else {
+        Some(data.as_bytes().to_vec())
+    }
+}
+
+fn post_data(request: &JSObject) -> Option<Vec<u8>> {
+    if let Some(entries) = request["postDataEntries"].as_array() {
+        let mut body = vec![];
+        for entry in entries {
+            body.extend(base64_decode(entry["bytes"].as_str().unwrap_or(""))?);
+        }
+        return Some(body);
+    }
+    request["postData"].as_str().map(|d| d.as_bytes().to_vec())
+}
+
+/// A route answering all requests to `origin` with a [`RequestHandler`]. Only
+/// http and https origins can be intercepted; the browser does not issue
+/// requests for unknown schemes.
+pub fn handler_route(origin: &str, handler: Arc<dyn RequestHandler>) -> Result<Route, String> {
+    let origin = origin.trim_end_matches('/');
+    if !(origin.starts_with("https://") || origin.starts_with("http://")) {
+        return Err(format!(
+            "Cannot handle {}: only http and https origins can be intercepted",
+            origin
+        ));
+    }
+    Ok(Route {
+        key: format!("origin {}", origin),
+        pattern: format!("{}/*", escape_pattern(origin)),
+        func: Arc::new(move |request| {
+            let handler = Arc::clone(&handler);
+            Box::pin(async move { InterceptAction::Fulfill(handler.handle(request).await) })
+        }),
+        inherit: true,
+    })
+}
+
+/// Escape the wildcards of a Fetch url pattern in `s`, so that it only
+/// matches itself.
+fn escape_pattern(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '*' | '?' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// The key of the route intercepting `pattern`.
+pub fn intercept_key(pattern: &str) -> String {
+    format!("intercept {}", pattern)
+}
+
+/// A route handing the requests matching `pattern` to `func`.
+pub fn intercept_route(pattern: &str, func: RouteFunc) -> Route {
+    Route {
+        key: intercept_key(pattern),
+        pattern: pattern.to_string(),
+        func,
+        inherit: true,
+    }
+}
+
+/// A route serving the files of an [`AssetProvider`] at [`ASSETS_ORIGIN`].
+pub fn assets_route(provider: Arc<dyn AssetProvider>) -> Route {
+    Route {
+        key: "assets".to_string(),
+        pattern: format!("{}/*", ASSETS_ORIGIN),
+        func: Arc::new(move |request| {
+            let provider = Arc::clone(&provider);
+            Box::pin(async move {
+                let path = request.url[ASSETS_ORIGIN.len()..]
+                    .split(['?', '#'])
+                    .next()
+                    .unwrap_or("")
+                    .trim_start_matches('/');
+                let path = percent_decode(if path.is_empty() { "index.html" } else { path });
+                InterceptAction::Fulfill(match provider.get(&path) {
+                    Some(asset) => Response::new(200, asset.data.into_owned())
+                        .header("Content-Type", &asset.mime_type),
+                    None => {
+                        Response::new(404, "404 Not Found").header("Content-Type", "text/plain")
+                    }
+                })
+            })
+        }),
+        inherit: false,
+    }
+}
+
+/// Match a url against a Fetch urlPattern: `*` matches any run of characters,
+/// `?` any single character and `\` escapes the next character.
+pub fn url_matches(pattern: &str, url: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let u: Vec<char> = url.chars().collect();
+    let (mut pi, mut ui) = (0, 0);
+    // Position after the last `*` and the url position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+    while ui < u.len() {
+        match p.get(pi) {
+            Some('*') => {
+                pi += 1;
+                backtrack = Some((pi, ui));
+                continue;
+            }
+            Some('?') => {
+                pi += 1;
+                ui += 1;
+                continue;
+            }
+            Some('\\') if p.get(pi + 1) == Some(&u[ui]) => {
+                pi += 2;
+                ui += 1;
+                continue;
+            }
+            Some(&c) if c != '\\' && c == u[ui] => {
+                pi += 1;
+                ui += 1;
+                continue;
+            }
+            _ => {}
+        }
+        match backtrack {
+            Some((bp, bu)) => {
+                pi = bp;
+                ui = bu + 1;
+                backtrack = Some((bp, bu + 1));
+            }
+            None => return false,
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+pub fn percent_decode(s: &str) -> String {
+    let hex = |b: u8| (b as char).to_digit(16).map(|d| d as u8);
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(h), Some(l)) = (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                out.push(h << 4 | l);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub fn base64_decode(data: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() / 4 * 3);
+    let (mut n, mut bits) = (0u32, 0);
+    for c in data.bytes().filter(|&c| c != b'=') {
+        let v = BASE64.iter().position(|&b| b == c)? as u32;
+        n = n << 6 | v;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+            n &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_url_matches() {
+        assert!(url_matches(
+            "https://alcro.invalid/*",
+            "https://alcro.invalid/"
+        ));
+        assert!(url_matches("*", "https://example.com/a?b"));
+        assert!(url_matches("*://*/api/*", "https://example.com/api/items"));
+        assert!(url_matches(
+            "https://example.com/?",
+            "https://example.com/a"
+        ));
+        assert!(url_matches("*\\?x", "https://example.com/a?x"));
+        assert!(!url_matches("*\\?x", "https://example.com/ax"));
+        assert!(!url_matches(
+            "https://alcro.invalid/*",
+            "https://alcro.invalid.com/"
+        ));
+        assert!(!url_matches(
+            "https://example.com/?",
+            "https://example.com/ab"
+        ));
+    }
+
+    #[test]
+    fn test_escape_pattern() {
+        assert_eq!(escape_pattern("https://a*b?c\\d"), "https://a\\*b\\?c\\\\d");
+        let pattern = format!("{}/*", escape_pattern("https://app*"));
+        assert!(url_matches(&pattern, "https://app*/index.html"));
+        assert!(!url_matches(&pattern, "https://app.evil.com/index.html"));
+        let pattern = format!("{}/*", escape_pattern("https://a?p"));
+        assert!(!url_matches(&pattern, "https://axp/"));
+    }
+
+    #[test]
+    fn test_encoding() {
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b""), "");
+        for data in [&b""[..], b"f", b"fo", b"foo", b"foob", b"fooba", b"foobar"] {
+            assert_eq!(base64_decode(&base64_encode(data)).unwrap(), data);
+        }
+        assert_eq!(percent_decode("a%20b%2"), "a b%2");
+    }
+}
@@ -0,0 +1,213 @@
+use super::{
+    add_css, fetch::percent_decode, reload, set_style_sheet_text, JSError, LoadOptions, Window,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::{Duration, SystemTime},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Modification time and size of every file under a directory, by path
+/// relative to it with `/` separators.
+type Snapshot = HashMap<String, (SystemTime, u64)>;
+
+/// Walks the directory with blocking calls, run it with `spawn_blocking`.
+fn snapshot(dir: &Path) -> Snapshot {
+    let mut files = Snapshot::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(d) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&d) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            let path = entry.path();
+            if meta.is_dir() {
+                dirs.push(path);
+            } else if let Ok(rel) = path.strip_prefix(dir) {
+                let rel = rel
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                let mtime = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                files.insert(rel, (mtime, meta.len()));
+            }
+        }
+    }
+    files
+}
+
+/// Files added, removed or modified between two snapshots.
+fn changes(old: &Snapshot, new: &Snapshot) -> Vec<String> {
+    let mut changed: Vec<String> = new
+        .iter()
+        .filter(|(path, meta)| old.get(*path) != Some(meta))
+        .map(|(path, _)| path.clone())
+        .chain(old.keys().filter(|path| !new.contains_key(*path)).cloned())
+        .collect();
+    changed.sort();
+    changed
+}
+
+async fn snapshot_off_runtime(dir: &Path) -> Option<Snapshot> {
+    let dir = dir.to_path_buf();
+    tokio::task::spawn_blocking(move || snapshot(&dir))
+        .await
+        .ok()
+}
+
+/// Returns true if the stylesheet at `url` is the file `path` of the watched
+/// directory, i.e. the path of the url ends with it.
+fn loads_file(url: &str, path: &str) -> bool {
+    let Some((_, rest)) = url.split_once("://") else {
+        return false;
+    };
+    let url_path = rest
+        .find('/')
+        .map_or("", |start| &rest[start..])
+        .split(['?', '#'])
+        .next()
+        .unwrap_or("");
+    percent_decode(url_path).ends_with(&format!("/{}", path))
+}
+
+/// Returns true if a stylesheet of the window was loaded from `path`.
+fn has_style_sheet(w: &Window, path: &str) -> bool {
+    w.style_sheets
+        .iter()
+        .any(|e| loads_file(&e.value().source_url, path))
+}
+
+/// Replace the rules of the stylesheets loaded from the changed CSS files with
+/// the new contents of the files, in every frame of the window.
+async fn swap_css(w: &Arc<Window>, dir: &Path, changed: &[String]) -> Result<(), JSError> {
+    for path in changed {
+        let file = dir.join(path);
+        let css = match tokio::task::spawn_blocking(move || std::fs::read_to_string(file)).await {
+            Ok(Ok(css)) => css,
+            _ => continue,
+        };
+        let sheets: Vec<(String, String)> = w
+            .style_sheets
+            .iter()
+            .filter(|e| loads_file(&e.value().source_url, path))
+            .map(|e| e.key().clone())
+            .collect();
+        for (session, id) in sheets {
+            let res = set_style_sheet_text(w, &session, &id, &css).await;
+            // Child sessions may be detaching while this runs
+            if session == w.session {
+                res?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reload the window, then add the CSS of load_css to the new documents.
+async fn reload_window(w: &Arc<Window>) -> Result<(), String> {
+    reload(w, true, &LoadOptions::default())
+        .await
+        .map_err(|e| e.to_string())?;
+    let styles = w.styles.lock().expect("Unable to lock").clone();
+    for css in styles {
+        add_css(w, &css).await.map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Watch `dir` for as long as `w` is open. When files change, the stylesheets
+/// loaded from them are updated in place if only CSS files that the window
+/// loaded changed, otherwise the window is reloaded.
+pub fn hot_reload(w: &Arc<Window>, dir: PathBuf) {
+    let w: Weak<Window> = Arc::downgrade(w);
+    tokio::spawn(async move {
+        let Some(mut files) = snapshot_off_runtime(&dir).await else {
+            return;
+        };
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            match w.upgrade() {
+                Some(w) if !w.is_closed() => {}
+                _ => break,
+            }
+            let Some(new_files) = snapshot_off_runtime(&dir).await else {
+                continue;
+            };
+            let changed = changes(&files, &new_files);
+            if changed.is_empty() {
+                continue;
+            }
+            let Some(w) = w.upgrade() else {
+                break;
+            };
+            // New files cannot be loaded by a stylesheet of the page yet
+            let css_only = changed.iter().all(|path| {
+                path.ends_with(".css")
+                    && files.contains_key(path)
+                    && new_files.contains_key(path)
+                    && has_style_sheet(&w, path)
+            });
+            files = new_files;
+            let res = if css_only {
+                swap_css(&w, &dir, &changed)
+                    .await
+                    .map_err(|e| e.to_string())
+            } else {
+                reload_window(&w).await
+            };
+            if let Err(e) = res {
+                eprintln!("Hot reload failed: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_changes() {
+        let t = SystemTime::UNIX_EPOCH;
+        let later = t + Duration::from_secs(1);
+        let old: Snapshot = [
+            ("index.html".to_string(), (t, 10)),
+            ("css/style.css".to_string(), (t, 5)),
+            ("old.js".to_string(), (t, 1)),
+        ]
+        .into();
+        let new: Snapshot = [
+            ("index.html".to_string(), (t, 10)),
+            ("css/style.css".to_string(), (later, 5)),
+            ("new.js".to_string(), (t, 1)),
+        ]
+        .into();
+        assert_eq!(changes(&old, &new), ["css/style.css", "new.js", "old.js"]);
+        assert!(changes(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_loads_file() {
+        assert!(loads_file(
+            "file:///home/me/app/css/style.css",
+            "css/style.css"
+        ));
+        assert!(loads_file(
+            "https://app.local/css/style.css?v=2",
+            "css/style.css"
+        ));
+        assert!(loads_file("file:///my%20app/a%20b.css", "a b.css"));
+        assert!(!loads_file(
+            "https://app.local/css/mystyle.css",
+            "style.css"
+        ));
+        assert!(!loads_file("https://style.css/", "style.css"));
+        assert!(!loads_file("inline.css", "inline.css"));
+    }
+}
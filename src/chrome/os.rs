@@ -154,3 +154,27 @@ pub use process_unix::*;
 mod process_windows;
 #[cfg(target_family = "windows")]
 pub use process_windows::*;
+
+/// Open `url` in the user's default browser.
+pub fn open_external(url: &str) -> std::io::Result<()> {
+    use std::process::{Command, Stdio};
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut c = Command::new("rundll32");
+        c.arg("url.dll,FileProtocolHandler");
+        c
+    };
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let mut command = Command::new("xdg-open");
+    let mut child = command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Reap the opener once it exits
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
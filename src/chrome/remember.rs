@@ -0,0 +1,143 @@
+use super::{bounds, displays, set_bounds, Bounds, JSError, Window, WindowEvent, WindowState};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::broadcast::error::RecvError;
+
+/// How long the bounds have to stay the same before they are saved, so that
+/// moving or resizing the window does not write the file for every step.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// The file the bounds of the window `key` are saved to in `dir`. Bytes that may
+/// not be valid in file names, and `_` itself, are escaped as `_` and two hex
+/// digits, so that different keys never share a file.
+pub fn bounds_file(dir: &Path, key: &str) -> PathBuf {
+    let mut name = String::from("alcro-bounds-");
+    for byte in key.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' {
+            name.push(byte as char);
+        } else {
+            name.push_str(&format!("_{:02X}", byte));
+        }
+    }
+    name.push_str(".json");
+    dir.join(name)
+}
+
+fn read_bounds(path: &Path) -> Option<Bounds> {
+    let text = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn write_bounds(path: &Path, b: &Bounds) {
+    // A temporary user data dir is removed when the browser exits
+    if path.parent().is_some_and(|dir| !dir.exists()) {
+        return;
+    }
+    let text = serde_json::to_string(b).expect("Bounds should serialize");
+    if let Err(e) = std::fs::write(path, text) {
+        eprintln!("Unable to save window bounds: {}", e);
+    }
+}
+
+/// Write the bounds off the runtime, file system calls block
+async fn save_bounds(path: &Path, b: Bounds) {
+    let path = path.to_path_buf();
+    let _ = tokio::task::spawn_blocking(move || write_bounds(&path, &b)).await;
+}
+
+/// Give the window its saved bounds, moved onto a visible display.
+/// A window never starts minimized.
+async fn restore_bounds(w: &Arc<Window>, saved: Bounds) -> Result<(), JSError> {
+    let displays = displays(w).await.unwrap_or_default();
+    let normal = Bounds {
+        window_state: WindowState::Normal,
+        ..saved
+    };
+    set_bounds(w, normal.clamp_to(&displays)).await?;
+    // Headless windows only have the normal state
+    let restore_state = matches!(
+        saved.window_state,
+        WindowState::Maximized | WindowState::Fullscreen
+    );
+    if restore_state && !w.chrome.headless {
+        set_bounds(w, saved.window_state.to_bounds()).await?;
+    }
+    Ok(())
+}
+
+/// Restore the bounds of `w` from `path`, then save them there whenever the
+/// window is moved, resized or changes its state, until it closes.
+///
+/// The saved position and size are those of the window in the normal state,
+/// so that a maximized window is restored maximized and still un-maximizes to
+/// where it was. It returns whether saved bounds were restored.
+pub async fn remember_bounds(w: &Arc<Window>, path: PathBuf) -> Result<bool, JSError> {
+    // Subscribed first so that no change after restoring is missed
+    let mut events = w.window_events();
+    let saved = {
+        let path = path.clone();
+        tokio::task::spawn_blocking(move || read_bounds(&path))
+            .await
+            .unwrap_or(None)
+    };
+    let mut current = match saved {
+        Some(saved) => {
+            restore_bounds(w, saved).await?;
+            saved
+        }
+        None => bounds(w).await.map_err(JSError::from)?,
+    };
+    let closed = w.closed_rx.clone();
+    tokio::spawn(async move {
+        let mut unsaved = false;
+        loop {
+            match tokio::time::timeout(SAVE_DELAY, events.recv()).await {
+                Ok(Ok(WindowEvent::BoundsChanged(b))) => {
+                    if b.window_state == WindowState::Normal {
+                        current = b;
+                    }
+                    current.window_state = b.window_state;
+                    unsaved = true;
+                }
+                Ok(Ok(WindowEvent::StateChanged(state))) => {
+                    current.window_state = state;
+                    unsaved = true;
+                }
+                Ok(Ok(_)) | Ok(Err(RecvError::Lagged(_))) => {}
+                Ok(Err(RecvError::Closed)) => break,
+                Err(_) => {
+                    if unsaved {
+                        save_bounds(&path, current).await;
+                        unsaved = false;
+                    }
+                    if *closed.borrow() {
+                        break;
+                    }
+                }
+            }
+        }
+        if unsaved {
+            save_bounds(&path, current).await;
+        }
+    });
+    Ok(saved.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_bounds_file() {
+        let dir = Path::new("data");
+        assert_eq!(bounds_file(dir, "main"), dir.join("alcro-bounds-main.json"));
+        assert_eq!(
+            bounds_file(dir, "../settings window"),
+            dir.join("alcro-bounds-_2E_2E_2Fsettings_20window.json")
+        );
+        assert_eq!(bounds_file(dir, "ü"), dir.join("alcro-bounds-_C3_BC.json"));
+        assert_ne!(bounds_file(dir, "a b"), bounds_file(dir, "a_b"));
+    }
+}
@@ -39,10 +39,21 @@
 
 mod chrome;
 use chrome::{
-    bind, bounds, close, eval, launch, load, load_css, load_js, new_window, set_bounds,
-    BindingFunc, LogSink, Window,
+    bind, bounds, bounds_file, call_js, close, close_browser, displays, emulate, eval,
+    eval_in_context, go_history, handler_route, hot_reload, intercept_key, intercept_route, launch,
+    launch_browser, load, load_css, load_js, navigation_history, new_window, open_window, reload,
+    remember_bounds, remove_route, reset_emulation, restore_window, set_bounds, set_close_handler,
+    set_navigation_policy, set_route, show_window, stop_loading, window_log_sink, BindingFunc,
+    CallInfo, Chrome, CloseFuture, LogSink, NewWindowFunc, NewWindowFuture, PolicyFunc,
+    PolicyFuture, Route, Source, Window, WindowConfig,
+};
+pub use chrome::{
+    Asset, AssetProvider, BindingOptions, BlockedCall, Bounds, CallJSError, DeviceProfile,
+    DisplayInfo, FailReason, HistoryEntry, InterceptAction, JSError, JSObject, JSResult, LoadError,
+    LoadOptions, LogOutput, NavigationAction, NavigationError, NavigationEvent, NavigationHistory,
+    OriginPolicy, Rect, Request, RequestHandler, Response, WaitUntil, WindowEvent, WindowOptions,
+    WindowState,
 };
-pub use chrome::{Bounds, JSError, JSObject, JSResult, LogOutput, WindowState};
 mod locate;
 pub use locate::tinyfiledialogs as dialog;
 use locate::{locate_chrome, LocateChromeError};
@@ -76,6 +87,48 @@ const DEFAULT_CHROME_ARGS: &[&str] = &[
     "--use-mock-keychain",
 ];
 
+/// Where a window that is shown after its content loads starts, far outside of
+/// any display. It replaces a position passed in the custom arguments, which
+/// the window is moved to once it is shown, see requested_position(). Where the
+/// window manager does not leave it there, it is minimized instead, see launch().
+const HIDDEN_WINDOW_POSITION: &str = "--window-position=-32000,-32000";
+
+/// The position passed with `--window-position` in the custom arguments. The
+/// browser uses the last one.
+fn requested_position(b: &UIBuilder) -> Option<(i32, i32)> {
+    let position = b
+        .custom_args
+        .iter()
+        .rev()
+        .find_map(|a| a.strip_prefix("--window-position="))?;
+    let (left, top) = position.split_once(',')?;
+    Some((left.trim().parse().ok()?, top.trim().parse().ok()?))
+}
+
+/// The arguments that open the first window of the browser in the builder's
+/// launch mode
+fn window_args(b: &UIBuilder) -> Vec<String> {
+    if b.custom_args.contains(&"--headless") {
+        return vec![];
+    }
+    // The window starts at about:blank and the content is loaded once via
+    // an explicit Page.navigate in launch(), which waits for the load
+    // event. Passing the real url here as well would load the page twice.
+    let mut args = vec!["--app=about:blank".to_string()];
+    if b.kiosk {
+        args.push("--kiosk".to_string());
+    } else if b.show_after_load {
+        // Maximized or fullscreen windows would be shown in place, so the
+        // state is set once the window is shown
+        args.push(HIDDEN_WINDOW_POSITION.to_string());
+    } else if b.state == WindowState::Maximized {
+        args.push("--start-maximized".to_string());
+    } else if b.state == WindowState::Fullscreen {
+        args.push("--start-fullscreen".to_string());
+    }
+    args
+}
+
 /// A browser window.
 ///
 /// The browser process is shared: [`UIBuilder::run()`] launches a browser with
@@ -83,6 +136,9 @@ const DEFAULT_CHROME_ARGS: &[&str] = &[
 /// same browser. The process exits when its last window is closed or dropped.
 pub struct UI {
     window: Arc<Window>,
+    // Handles given out by alcro itself (e.g. in a CallContext) do not own
+    // the window and leave it open when dropped.
+    owned: bool,
 }
 
 /// Error in launching a UI window
@@ -103,44 +159,67 @@ pub enum UILaunchError {
     /// Cannot create the log file
     #[error("Cannot create log file: {0}")]
     LogFileCreationError(std::io::Error),
+    /// A request handler was registered for an origin that cannot be intercepted
+    #[error("{0}")]
+    InvalidHandlerOrigin(String),
+    /// The initial content did not load, e.g. because the server is not up
+    /// ([`LoadError::Navigation`]) or it took too long ([`LoadError::Timeout`])
+    #[error("Error when loading the content: {0}")]
+    LoadError(LoadError),
 }
 
-impl UI {
-    async fn new(
-        url: &str,
-        dir: Option<&std::path::Path>,
-        width: i32,
-        height: i32,
-        custom_args: &[&str],
-        log_output: Option<&LogOutput>,
-    ) -> Result<UI, UILaunchError> {
-        let _tmpdir;
-        let dir = match dir {
+/// Everything needed to start the browser process, taken from a [`UIBuilder`]
+struct LaunchSetup {
+    chrome_path: String,
+    user_data_dir: std::path::PathBuf,
+    args: Vec<String>,
+    log_sink: Option<LogSink>,
+    tmpdir: Option<tempfile::TempDir>,
+    config: WindowConfig,
+}
+
+impl LaunchSetup {
+    /// A `windowless` browser starts without a window and keeps running when its
+    /// last window closes.
+    fn new(b: &UIBuilder<'_>, windowless: bool) -> Result<Self, UILaunchError> {
+        let routes = b
+            .handlers
+            .iter()
+            .map(|(origin, handler)| handler_route(origin, Arc::clone(handler)))
+            .collect::<Result<Vec<Route>, _>>()
+            .map_err(UILaunchError::InvalidHandlerOrigin)?;
+        let config = WindowConfig {
+            origin_policy: b.origin_policy.clone(),
+            routes,
+            navigation_policy: b.navigation_policy.clone(),
+            new_window_handler: b.new_window_handler.clone(),
+            log_sink: None,
+            load_options: b.load_options.clone(),
+        };
+        let custom_args = b.custom_args;
+
+        let tmpdir;
+        let dir = match b.dir {
             Some(dir) => {
-                _tmpdir = None;
+                tmpdir = None;
                 dir
             }
             None => {
-                _tmpdir = Some(tempfile::TempDir::new()?);
-                _tmpdir.as_ref().unwrap().path()
+                tmpdir = Some(tempfile::TempDir::new()?);
+                tmpdir.as_ref().unwrap().path()
             }
         };
 
-        let mut args = Vec::from(DEFAULT_CHROME_ARGS);
-        let user_data_dir_arg = format!("--user-data-dir={}", dir.to_str().unwrap());
-        args.push(&user_data_dir_arg);
-        let window_size_arg = format!("--window-size={},{}", width, height);
-        args.push(&window_size_arg);
-        for arg in custom_args {
-            args.push(arg)
-        }
-        args.push("--remote-debugging-pipe");
+        let mut args: Vec<String> = DEFAULT_CHROME_ARGS.iter().map(|a| a.to_string()).collect();
+        args.push(format!("--user-data-dir={}", dir.to_str().unwrap()));
+        args.push(format!("--window-size={},{}", b.width, b.height));
+        args.extend(custom_args.iter().map(|a| a.to_string()));
+        args.push("--remote-debugging-pipe".to_string());
 
-        // The window starts at about:blank and the content is loaded once via
-        // an explicit Page.navigate in launch(), which waits for the load
-        // event. Passing the real url here as well would load the page twice.
-        if !custom_args.contains(&"--headless") {
-            args.push("--app=about:blank");
+        if windowless {
+            args.push("--no-startup-window".to_string());
+        } else {
+            args.extend(window_args(b));
         }
         let chrome_path = match std::env::var("ALCRO_BROWSER_PATH") {
             Ok(path) => {
@@ -152,20 +231,227 @@ impl UI {
             }
             Err(_) => locate_chrome()?,
         };
-        let log_sink = match log_output {
-            None => None,
-            Some(LogOutput::Stdout) => Some(LogSink::Stdout),
-            Some(LogOutput::Stderr) => Some(LogSink::Stderr),
-            Some(LogOutput::File(path)) => Some(LogSink::File(std::sync::Mutex::new(
-                std::fs::File::create(path).map_err(UILaunchError::LogFileCreationError)?,
-            ))),
+        let log_sink = b
+            .log_output
+            .as_ref()
+            .map(LogSink::open)
+            .transpose()
+            .map_err(UILaunchError::LogFileCreationError)?;
+        Ok(LaunchSetup {
+            chrome_path,
+            user_data_dir: dir.to_path_buf(),
+            args,
+            log_sink,
+            tmpdir,
+            config,
+        })
+    }
+}
+
+/// A browser process, independent of its windows.
+///
+/// Launched with [`UIBuilder::run_browser()`], the browser starts without a window
+/// and keeps running while there is a `Browser` handle, even when it has no
+/// windows. This keeps it warm for background apps that only show a window now and
+/// then: opening a window in a running browser is much faster than launching one.
+///
+/// Dropping the last handle kills the browser if it has no windows left; otherwise
+/// it exits when its last window closes. To shut down gracefully call
+/// [`Browser::close()`] and [`Browser::wait_finish()`].
+pub struct Browser {
+    chrome: Arc<Chrome>,
+    config: WindowConfig,
+    hot_reload_dir: Option<std::path::PathBuf>,
+}
+
+impl Browser {
+    /// Open a window with the builder's settings and wait for its content to load.
+    /// It returns Err if it fails, like [`UI::load()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![windows_subsystem = "windows"]
+    /// use alcro::{Content, UIBuilder};
+    /// # tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    /// let browser = UIBuilder::new()
+    ///     .custom_args(&["--headless"])
+    ///     .run_browser()
+    ///     .await
+    ///     .expect("Unable to launch");
+    /// let ui = browser
+    ///     .new_window(Content::Html("<html><body>Hello</body></html>"))
+    ///     .await
+    ///     .expect("Unable to open window");
+    /// assert_eq!(browser.windows().len(), 1);
+    /// ui.close().await;
+    /// ui.wait_finish().await;
+    /// // The browser keeps running without windows
+    /// assert!(!browser.done());
+    /// browser.close().await;
+    /// browser.wait_finish().await;
+    /// # });
+    /// ```
+    pub async fn new_window(&self, content: Content<'_>) -> Result<UI, LoadError> {
+        let window = open_window(
+            &self.chrome,
+            &content.source(),
+            &self.config,
+            &WindowOptions::default(),
+        )
+        .await?;
+        if let Some(dir) = &self.hot_reload_dir {
+            hot_reload(&window, dir.clone());
+        }
+        Ok(UI {
+            window,
+            owned: true,
+        })
+    }
+
+    /// Open a window with the builder's settings and the given size, position, state
+    /// and log output, see [`UI::new_window_with_options()`].
+    pub async fn new_window_with_options(
+        &self,
+        content: Content<'_>,
+        options: &WindowOptions,
+    ) -> Result<UI, UILaunchError> {
+        let mut config = self.config.clone();
+        if let Some(sink) = window_log_sink(options).map_err(UILaunchError::LogFileCreationError)? {
+            config.log_sink = Some(sink);
+        }
+        let window = open_window(&self.chrome, &content.source(), &config, options)
+            .await
+            .map_err(UILaunchError::LoadError)?;
+        if let Some(dir) = &self.hot_reload_dir {
+            hot_reload(&window, dir.clone());
+        }
+        Ok(UI {
+            window,
+            owned: true,
+        })
+    }
+
+    /// Returns the open windows of the browser. The handles do not own their windows:
+    /// dropping them leaves the windows open.
+    ///
+    /// Only windows that still have a [`UI`] handle are listed; a window whose `UI`
+    /// was dropped is closed.
+    pub fn windows(&self) -> Vec<UI> {
+        self.chrome
+            .windows()
+            .into_iter()
+            .map(|window| UI {
+                window,
+                owned: false,
+            })
+            .collect()
+    }
+
+    /// Evaluate js code in every open window and return the results, in the order of
+    /// [`Browser::windows()`].
+    pub async fn broadcast(&self, js: &str) -> Vec<JSResult> {
+        let mut results = vec![];
+        for window in self.chrome.windows() {
+            results.push(eval(&window, js).await);
+        }
+        results
+    }
+
+    /// Returns true if the browser has exited
+    pub fn done(&self) -> bool {
+        self.chrome.is_closed()
+    }
+
+    /// Close all windows and shut the browser down gracefully
+    pub async fn close(&self) {
+        close_browser(&self.chrome).await
+    }
+
+    /// Wait for the browser process to exit
+    pub async fn wait_finish(&self) {
+        self.chrome.wait_exit().await
+    }
+}
+
+impl Clone for Browser {
+    fn clone(&self) -> Self {
+        self.chrome.retain();
+        Browser {
+            chrome: Arc::clone(&self.chrome),
+            config: self.config.clone(),
+            hot_reload_dir: self.hot_reload_dir.clone(),
+        }
+    }
+}
+
+impl Drop for Browser {
+    fn drop(&mut self) {
+        self.chrome.release();
+    }
+}
+
+impl UI {
+    async fn new(b: &UIBuilder<'_>) -> Result<UI, UILaunchError> {
+        let setup = LaunchSetup::new(b, false)?;
+        let args: Vec<&str> = setup.args.iter().map(String::as_str).collect();
+        let source = b.content.source();
+        let headless = b.custom_args.contains(&"--headless");
+        let hidden = !headless && b.show_after_load && !b.kiosk;
+        let window = launch(
+            &setup.chrome_path,
+            &args,
+            &source,
+            &setup.config,
+            hidden,
+            setup.log_sink,
+            setup.tmpdir,
+        )
+        .await
+        .map_err(|e| match e {
+            LoadError::DevTools(e) => UILaunchError::ChromeInitError(e),
+            e => UILaunchError::LoadError(e),
+        })?;
+        if hidden {
+            // Back to the normal state if it had to be minimized, still out of
+            // sight, so that remembered bounds can be applied
+            restore_window(&window).await?;
+        }
+        let restored = match b.remember_bounds {
+            Some(key) => remember_bounds(&window, bounds_file(&setup.user_data_dir, key)).await?,
+            None => false,
         };
-        let window = launch(&chrome_path, &args, url, log_sink, _tmpdir).await?;
-        Ok(UI { window })
+        if !headless {
+            if hidden && !restored {
+                show_window(&window, b.width, b.height, requested_position(b)).await?;
+            }
+            // Remembered bounds take the place of the initial state, except in kiosk mode
+            let state = if b.kiosk {
+                WindowState::Fullscreen
+            } else if restored {
+                WindowState::Normal
+            } else {
+                b.state
+            };
+            if state != WindowState::Normal {
+                set_bounds(&window, state.to_bounds()).await?;
+            }
+        }
+        if let Some(dir) = b.hot_reload_dir {
+            hot_reload(&window, dir.to_path_buf());
+        }
+        Ok(UI {
+            window,
+            owned: true,
+        })
     }
 
     /// Open another window in the same browser process and wait for its
-    /// content to load. It returns Err if it fails.
+    /// content to load. It returns Err if it fails, including when the content
+    /// fails to load (see [`UI::load()`]); the window is closed then.
+    ///
+    /// The new window starts with the origin policy and the origin handlers of this
+    /// window.
     ///
     /// # Examples
     ///
@@ -183,17 +469,61 @@ impl UI {
     /// assert_eq!(ui2.eval("document.body.innerText").await.unwrap(), "second");
     /// # });
     /// ```
-    pub async fn new_window(&self, content: Content<'_>) -> Result<UI, JSError> {
-        let html: String;
-        let url = match content {
-            Content::Url(u) => u,
-            Content::Html(h) => {
-                html = format!("data:text/html,{}", h);
-                &html
-            }
-        };
-        let window = new_window(&self.window, url).await?;
-        Ok(UI { window })
+    pub async fn new_window(&self, content: Content<'_>) -> Result<UI, LoadError> {
+        let window = new_window(
+            &self.window,
+            &content.source(),
+            &self.window.inherited_config(),
+            &WindowOptions::default(),
+        )
+        .await?;
+        Ok(UI {
+            window,
+            owned: true,
+        })
+    }
+
+    /// Like [`UI::new_window()`], but the window opens with the size, position and
+    /// state of `options`, or as a tab. These are passed to the browser when it creates
+    /// the window, so it does not show up at another place first. It returns Err if the
+    /// log file cannot be created or the content fails to load.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![windows_subsystem = "windows"]
+    /// use alcro::{Content, LogOutput, UIBuilder, WindowOptions, WindowState};
+    /// # tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    /// let ui = UIBuilder::new().custom_args(&["--headless"]).run().await.expect("Unable to launch");
+    /// let inspector = ui
+    ///     .new_window_with_options(
+    ///         Content::Html("<html><body>Inspector</body></html>"),
+    ///         WindowOptions::new()
+    ///             .size(400, 600)
+    ///             .position(100, 100)
+    ///             .state(WindowState::Normal)
+    ///             .log_output(LogOutput::Stderr),
+    ///     )
+    ///     .await
+    ///     .expect("Unable to open window");
+    /// # });
+    /// ```
+    pub async fn new_window_with_options(
+        &self,
+        content: Content<'_>,
+        options: &WindowOptions,
+    ) -> Result<UI, UILaunchError> {
+        let mut config = self.window.inherited_config();
+        if let Some(sink) = window_log_sink(options).map_err(UILaunchError::LogFileCreationError)? {
+            config.log_sink = Some(sink);
+        }
+        let window = new_window(&self.window, &content.source(), &config, options)
+            .await
+            .map_err(UILaunchError::LoadError)?;
+        Ok(UI {
+            window,
+            owned: true,
+        })
     }
 
     /// Returns true if this window is closed
@@ -207,22 +537,416 @@ impl UI {
     }
 
     /// Close this window gracefully. The browser process exits when its last
-    /// window is closed.
+    /// window is closed, unless a [`Browser`] handle keeps it running.
     pub async fn close(&self) {
         close(&self.window).await
     }
 
-    /// Load content in the window and wait for the page to load. It returns Err if it fails.
-    pub async fn load(&self, content: Content<'_>) -> Result<(), JSError> {
-        let html: String;
-        let url = match content {
-            Content::Url(u) => u,
-            Content::Html(h) => {
-                html = format!("data:text/html,{}", h);
-                &html
-            }
-        };
-        load(&self.window, url).await
+    /// Answer the window's requests to `origin` (e.g. `https://app.local`) with a
+    /// Rust handler instead of the network. It returns Err if it fails.
+    ///
+    /// The handler receives the method, url, headers and body of each request and
+    /// returns the full response, which gives the page a REST-like backend without a
+    /// TCP port. Requests are intercepted with the DevTools Fetch domain, in the
+    /// window's iframes too, and each runs on its own tokio task. Registering the same
+    /// origin again replaces the handler. Windows opened with [`UI::new_window()`]
+    /// inherit the handlers.
+    ///
+    /// Only `http` and `https` origins can be handled: the browser does not request
+    /// urls of unknown schemes like `app://`. Use a reserved domain such as
+    /// `https://app.local` or a subdomain of `.invalid` so nothing is sent out if a
+    /// request is not intercepted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![windows_subsystem = "windows"]
+    /// use alcro::{Content, Request, Response, UIBuilder};
+    /// # tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    /// let ui = UIBuilder::new().custom_args(&["--headless"]).run().await.expect("Unable to launch");
+    /// ui.handle_origin("https://app.local", |req: Request| async move {
+    ///     Response::new(200, format!("{} {}", req.method, req.url))
+    ///         .header("Content-Type", "text/plain")
+    /// })
+    /// .await
+    /// .expect("Unable to register handler");
+    /// ui.load(Content::Url("https://app.local/items")).await.unwrap();
+    /// assert_eq!(
+    ///     ui.eval("document.body.innerText").await.unwrap(),
+    ///     "GET https://app.local/items"
+    /// );
+    /// # });
+    /// ```
+    pub async fn handle_origin(
+        &self,
+        origin: &str,
+        handler: impl RequestHandler,
+    ) -> Result<(), JSError> {
+        let route = handler_route(origin, Arc::new(handler)).map_err(JSObject::String)?;
+        set_route(&self.window, route).await.map_err(JSError::from)
+    }
+
+    /// Intercept the window's requests whose url matches `pattern` and let `f` decide
+    /// what happens to each of them. It returns Err if it fails.
+    ///
+    /// In the pattern `*` matches any run of characters, `?` a single character and
+    /// `\` escapes the next character, e.g. `*://*/api/*`. The function receives the
+    /// request and returns an [`InterceptAction`] to let it through, send a modified
+    /// request (e.g. with other headers or another url), answer it with a mock
+    /// [`Response`] or fail it. Requests from the window's iframes are intercepted too,
+    /// and each runs on its own tokio task.
+    ///
+    /// When several interceptions or [origin handlers](UI::handle_origin()) match a
+    /// request, the one registered first decides. Intercepting the same pattern again
+    /// replaces the function. Windows opened with [`UI::new_window()`] inherit the
+    /// interceptions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![windows_subsystem = "windows"]
+    /// use alcro::{Content, FailReason, InterceptAction, Response, UIBuilder};
+    /// # tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    /// let ui = UIBuilder::new().custom_args(&["--headless"]).run().await.expect("Unable to launch");
+    /// ui.intercept("https://example.com/*", |req| async move {
+    ///     if req.url.ends_with("/track") {
+    ///         InterceptAction::Fail(FailReason::BlockedByClient)
+    ///     } else {
+    ///         InterceptAction::Fulfill(
+    ///             Response::new(200, "<html><body>mocked</body></html>")
+    ///                 .header("Content-Type", "text/html"),
+    ///         )
+    ///     }
+    /// })
+    /// .await
+    /// .expect("Unable to intercept");
+    /// ui.load(Content::Url("https://example.com/")).await.unwrap();
+    /// assert_eq!(ui.eval("document.body.innerText").await.unwrap(), "mocked");
+    /// assert!(ui.eval("fetch('/track')").await.is_err());
+    /// # });
+    /// ```
+    pub async fn intercept<F, Fut>(&self, pattern: &str, f: F) -> Result<(), JSError>
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = InterceptAction> + Send + 'static,
+    {
+        let route = intercept_route(pattern, Arc::new(move |req| Box::pin(f(req))));
+        set_route(&self.window, route).await.map_err(JSError::from)
+    }
+
+    /// Stop intercepting the requests matching `pattern`, see [`UI::intercept()`].
+    /// It returns Err if it fails.
+    pub async fn remove_intercept(&self, pattern: &str) -> Result<(), JSError> {
+        remove_route(&self.window, &intercept_key(pattern))
+            .await
+            .map_err(JSError::from)
+    }
+
+    /// Decide where the page may navigate. It returns Err if it fails.
+    ///
+    /// Before the window navigates to another page, e.g. because the user clicked a
+    /// link or the page set `location`, `f` receives the url and returns whether to
+    /// navigate, stay on the current page or open the url in the user's default
+    /// browser instead. This keeps an app from losing its state to an external link.
+    ///
+    /// The policy applies to navigations of the top-level page that go through the
+    /// network (http and https urls, including [handled origins](UI::handle_origin())
+    /// and redirects), not to iframes, and not to content loaded with [`UI::load()`]
+    /// and the like. Windows opened with [`UI::new_window()`] inherit the policy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![windows_subsystem = "windows"]
+    /// use alcro::{Content, NavigationAction, UIBuilder};
+    /// # tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    /// let ui = UIBuilder::new().custom_args(&["--headless"]).run().await.expect("Unable to launch");
+    /// ui.set_navigation_policy(|url| async move {
+    ///     if url.starts_with("https://app.local/") {
+    ///         NavigationAction::Allow
+    ///     } else {
+    ///         NavigationAction::OpenExternal
+    ///     }
+    /// })
+    /// .await
+    /// .expect("Unable to set the policy");
+    /// # });
+    /// ```
+    pub async fn set_navigation_policy<F, Fut>(&self, f: F) -> Result<(), JSError>
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = NavigationAction> + Send + 'static,
+    {
+        set_navigation_policy(&self.window, Some(policy_func(f)))
+            .await
+            .map_err(JSError::from)
+    }
+
+    /// Remove the navigation policy, allowing all navigations. It returns Err if it fails.
+    pub async fn clear_navigation_policy(&self) -> Result<(), JSError> {
+        set_navigation_policy(&self.window, None)
+            .await
+            .map_err(JSError::from)
+    }
+
+    /// Ask an async handler before the user closes the window, so the app can prompt
+    /// about unsaved changes, clean up or cancel the close. The window only closes when
+    /// the handler returns true. It returns Err if it fails.
+    ///
+    /// The handler is also asked when the user leaves the page by following a link
+    /// or reloading, as the browser does not tell these apart from closing. Loads
+    /// started from rust (e.g. [`UI::load()`]) and [`UI::close()`] do not ask it.
+    /// A later call replaces the handler.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![windows_subsystem = "windows"]
+    /// use alcro::UIBuilder;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// # tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    /// let ui = UIBuilder::new().custom_args(&["--headless"]).run().await.expect("Unable to launch");
+    /// let unsaved = Arc::new(AtomicBool::new(true));
+    /// ui.on_close_requested(move || {
+    ///     let unsaved = unsaved.clone();
+    ///     async move {
+    ///         // E.g. ask with alcro::dialog whether to discard the changes
+    ///         !unsaved.load(Ordering::SeqCst)
+    ///     }
+    /// })
+    /// .await
+    /// .unwrap();
+    /// # });
+    /// ```
+    pub async fn on_close_requested<F, Fut>(&self, f: F) -> Result<(), JSError>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = bool> + Send + 'static,
+    {
+        set_close_handler(
+            &self.window,
+            Arc::new(move || -> CloseFuture { Box::pin(f()) }),
+        )
+        .await
+    }
+
+    /// Handle the windows the page opens, with `window.open()` or a link or form
+    /// targeting a new window. `f` receives a [`UI`] for each of them once it is set
+    /// up: it has the opener's bindings, scripts added by [`UI::load_js()`], request
+    /// handlers, interceptions and policies, and new windows it opens go to `f` as
+    /// well.
+    ///
+    /// The window is held before its first page until the future returned by `f`
+    /// completes, so do not wait for the page in `f`. Keep the `UI` to keep the
+    /// window; drop it (or close it) in `f` to deny the window, which is then closed
+    /// without loading anything. Without a handler the page's windows are not tracked
+    /// by alcro.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![windows_subsystem = "windows"]
+    /// use alcro::UIBuilder;
+    /// # tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    /// let ui = UIBuilder::new().custom_args(&["--headless"]).run().await.expect("Unable to launch");
+    /// let (send, mut recv) = tokio::sync::mpsc::unbounded_channel();
+    /// ui.on_new_window(move |popup| {
+    ///     let send = send.clone();
+    ///     async move {
+    ///         let _ = send.send(popup);
+    ///     }
+    /// })
+    /// .await
+    /// .unwrap();
+    /// ui.eval("window.open('about:blank')").await.unwrap();
+    /// let popup = recv.recv().await.unwrap();
+    /// assert_eq!(popup.eval("1+1").await.unwrap(), 2);
+    /// # });
+    /// ```
+    pub async fn on_new_window<F, Fut>(&self, f: F) -> Result<(), JSError>
+    where
+        F: Fn(UI) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.window
+            .set_new_window_handler(Some(new_window_func(f)))
+            .await
+    }
+
+    /// Load content in the window and wait for the page to load. It returns Err if it fails,
+    /// or if the page does not load within 30 seconds.
+    ///
+    /// A page that cannot be fetched (e.g. the server is not up) or that is answered with
+    /// an HTTP error status fails with [`LoadError::Navigation`], which has the network
+    /// error and the status. The browser's error page is shown in the window then.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![windows_subsystem = "windows"]
+    /// use alcro::{Content, LoadError, UIBuilder};
+    /// # tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    /// let ui = UIBuilder::new().custom_args(&["--headless"]).run().await.expect("Unable to launch");
+    /// match ui.load(Content::Url("https://server.invalid/")).await {
+    ///     Err(LoadError::Navigation(e)) => {
+    ///         assert_eq!(e.net_error.as_deref(), Some("net::ERR_NAME_NOT_RESOLVED"));
+    ///         ui.load(Content::Html("<html><body>The server is not up</body></html>"))
+    ///             .await
+    ///             .unwrap();
+    ///     }
+    ///     res => panic!("Unexpected result {:?}", res),
+    /// }
+    /// # });
+    /// ```
+    pub async fn load(&self, content: Content<'_>) -> Result<(), LoadError> {
+        self.load_with_options(content, &LoadOptions::default())
+            .await
+    }
+
+    /// Load content in the window and wait for it as `options` say: for the
+    /// DOMContentLoaded or load event, for the network to be idle or for a JS predicate
+    /// to hold. It returns Err if it fails or if the wait exceeds the timeout.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![windows_subsystem = "windows"]
+    /// use alcro::{Content, LoadOptions, UIBuilder, WaitUntil};
+    /// use std::time::Duration;
+    /// # tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    /// let ui = UIBuilder::new().custom_args(&["--headless"]).run().await.expect("Unable to launch");
+    /// ui.load_with_options(
+    ///     Content::Html("<script>setTimeout(() => window.ready = true, 200)</script>"),
+    ///     LoadOptions::new()
+    ///         .wait_until(WaitUntil::Predicate("window.ready".to_string()))
+    ///         .timeout(Duration::from_secs(5)),
+    /// )
+    /// .await
+    /// .expect("Unable to load");
+    /// assert_eq!(ui.eval("window.ready").await.unwrap(), true);
+    /// # });
+    /// ```
+    pub async fn load_with_options(
+        &self,
+        content: Content<'_>,
+        options: &LoadOptions,
+    ) -> Result<(), LoadError> {
+        load(&self.window, &content.source(), options).await
+    }
+
+    /// Reload the page and wait for it to load. It returns Err if it fails, like
+    /// [`UI::load()`]. With `ignore_cache` the page and its resources are fetched
+    /// again even if they are cached. Bindings and scripts added by [`UI::load_js()`]
+    /// stay in place.
+    pub async fn reload(&self, ignore_cache: bool) -> Result<(), LoadError> {
+        reload(&self.window, ignore_cache, &LoadOptions::default()).await
+    }
+
+    /// Go back to the previous page of the session history and wait for it to load.
+    /// It returns `Ok(false)` if there is no previous page and Err if it fails, like
+    /// [`UI::load()`]. A page restored from the back/forward cache is loaded as soon as
+    /// it is shown.
+    pub async fn go_back(&self) -> Result<bool, LoadError> {
+        go_history(&self.window, -1, &LoadOptions::default()).await
+    }
+
+    /// Go forward to the next page of the session history and wait for it to load.
+    /// It returns `Ok(false)` if there is no next page and Err if it fails, like
+    /// [`UI::load()`].
+    pub async fn go_forward(&self) -> Result<bool, LoadError> {
+        go_history(&self.window, 1, &LoadOptions::default()).await
+    }
+
+    /// Stop loading the current page, like the browser's stop button. It returns Err
+    /// if it fails.
+    pub async fn stop_loading(&self) -> Result<(), JSError> {
+        stop_loading(&self.window).await
+    }
+
+    /// Returns the url of the current page
+    pub async fn current_url(&self) -> Result<String, JSError> {
+        let history = navigation_history(&self.window).await?;
+        Ok(history
+            .entries
+            .get(history.current_index)
+            .map(|e| e.url.clone())
+            .unwrap_or_default())
+    }
+
+    /// Returns the title of the current page
+    pub async fn title(&self) -> Result<String, JSError> {
+        let history = navigation_history(&self.window).await?;
+        Ok(history
+            .entries
+            .get(history.current_index)
+            .map(|e| e.title.clone())
+            .unwrap_or_default())
+    }
+
+    /// Returns the session history of the window, i.e. the pages
+    /// [`UI::go_back()`] and [`UI::go_forward()`] move between.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![windows_subsystem = "windows"]
+    /// use alcro::{Content, UIBuilder};
+    /// # tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    /// let ui = UIBuilder::new().custom_args(&["--headless"]).run().await.expect("Unable to launch");
+    /// let history = ui.navigation_history().await.unwrap();
+    /// for (i, entry) in history.entries.iter().enumerate() {
+    ///     let current = if i == history.current_index { "*" } else { " " };
+    ///     println!("{} {} {}", current, entry.title, entry.url);
+    /// }
+    /// // The current page is the newest one
+    /// assert!(!ui.go_forward().await.unwrap());
+    /// # });
+    /// ```
+    pub async fn navigation_history(&self) -> Result<NavigationHistory, JSError> {
+        navigation_history(&self.window).await
+    }
+
+    /// Subscribe to the navigation events of the window's main frame: started,
+    /// committed, DOMContentLoaded, load, finished and same-document navigations.
+    ///
+    /// Events are buffered per receiver; a receiver that falls behind by more than 16
+    /// events skips the oldest ones.
+    pub fn navigation_events(&self) -> tokio::sync::broadcast::Receiver<NavigationEvent> {
+        self.window.navigation_events()
+    }
+
+    /// Subscribe to changes of the window: moving and resizing, changes of its
+    /// [`WindowState`], focus and blur, and the page becoming visible or hidden.
+    ///
+    /// The events are reported by a script the window runs in every top level
+    /// document, so a window reports nothing while it shows no document, e.g. while a
+    /// navigation fails. Only changes after subscribing are received; use
+    /// [`UI::bounds()`] for the current bounds. In headless mode the window state is
+    /// always reported as [`WindowState::Normal`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![windows_subsystem = "windows"]
+    /// use alcro::{UIBuilder, WindowEvent, WindowState};
+    /// # tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    /// let ui = UIBuilder::new().custom_args(&["--headless"]).run().await.expect("Unable to launch");
+    /// let mut events = ui.window_events();
+    /// tokio::spawn(async move {
+    ///     while let Ok(event) = events.recv().await {
+    ///         match event {
+    ///             WindowEvent::BoundsChanged(bounds) => println!("Save layout {:?}", bounds),
+    ///             WindowEvent::StateChanged(WindowState::Minimized) => println!("Pause rendering"),
+    ///             _ => {}
+    ///         }
+    ///     }
+    /// });
+    /// # });
+    /// ```
+    pub fn window_events(&self) -> tokio::sync::broadcast::Receiver<WindowEvent> {
+        self.window.window_events()
     }
 
     /// Bind a rust function so that JS code can use it. It returns Err if it fails.
@@ -231,13 +955,16 @@ impl UI {
     /// result (generally by using an `async move` block body). Each invocation from JS
     /// runs as its own tokio task, so bindings can be called concurrently and may await
     /// freely; use [`tokio::task::spawn_blocking`] inside the binding for CPU heavy or
-    /// blocking work.
+    /// blocking work. To limit how many calls run at once use [`UI::bind_with_options()`].
+    ///
+    /// The function is also exposed in the window's iframes, including out-of-process
+    /// ones. Results are returned to the frame that made the call.
     ///
     /// # Arguments
     ///
     /// * `name` - Name of the function
     /// * `f` - The function. It should take a [`Vec`] of [`JSObject`] arguments by value
-    ///         and return a [`Future`] for the [`JSResult`]
+    ///   and return a [`Future`] for the [`JSResult`]
     ///
     /// # Examples
     ///
@@ -267,8 +994,97 @@ impl UI {
         F: Fn(Vec<JSObject>) -> Fut + Sync + Send + 'static,
         Fut: Future<Output = JSResult> + Send + 'static,
     {
-        let func: BindingFunc = Arc::new(move |args| Box::pin(f(args)));
-        bind(&self.window, name, func).await
+        let func: BindingFunc = Arc::new(move |_, args| Box::pin(f(args)));
+        bind(&self.window, name, func, BindingOptions::default()).await
+    }
+
+    /// Bind a rust function that also receives the [`CallContext`] of the JS caller.
+    /// It returns Err if it fails.
+    ///
+    /// This works like [`UI::bind()`]. The context tells which frame made the call, so
+    /// the function can reject calls from unexpected origins or reply to the caller.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![windows_subsystem = "windows"]
+    /// use alcro::{Content, UIBuilder};
+    ///
+    /// # tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    /// let ui = UIBuilder::new()
+    ///     .content(Content::Html("<html><body>main</body></html>"))
+    ///     .custom_args(&["--headless"])
+    ///     .run().await.expect("Unable to launch");
+    /// ui.bind_with_context("caller", |ctx, _args| async move {
+    ///     let text = ctx.eval("document.body.innerText").await?;
+    ///     Ok(serde_json::json!({ "origin": ctx.origin(), "text": text }))
+    /// }).await.expect("Unable to bind function");
+    /// assert_eq!(ui.eval("(async () => (await caller()).text)();").await.unwrap(), "main");
+    /// # });
+    /// ```
+    pub async fn bind_with_context<F, Fut>(&self, name: &str, f: F) -> Result<(), JSError>
+    where
+        F: Fn(CallContext, Vec<JSObject>) -> Fut + Sync + Send + 'static,
+        Fut: Future<Output = JSResult> + Send + 'static,
+    {
+        self.bind_with_options(name, &BindingOptions::default(), f)
+            .await
+    }
+
+    /// Bind a rust function with the given [`BindingOptions`]. It returns Err if it fails.
+    ///
+    /// The function receives the [`CallContext`] like with [`UI::bind_with_context()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![windows_subsystem = "windows"]
+    /// use alcro::{BindingOptions, Content, OriginPolicy, UIBuilder};
+    ///
+    /// # tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    /// let ui = UIBuilder::new()
+    ///     .content(Content::Html("<html><body></body></html>"))
+    ///     .custom_args(&["--headless"])
+    ///     .run().await.expect("Unable to launch");
+    /// ui.bind_with_options(
+    ///     "secret",
+    ///     BindingOptions::new().origin_policy(OriginPolicy::allow_list(&["https://app.example"])),
+    ///     |_, _| async move { Ok("hidden".into()) },
+    /// ).await.expect("Unable to bind function");
+    /// // The inline page has the origin `null`, so the binding is not exposed to it
+    /// assert_eq!(ui.eval("typeof secret").await.unwrap(), "undefined");
+    /// # });
+    /// ```
+    pub async fn bind_with_options<F, Fut>(
+        &self,
+        name: &str,
+        options: &BindingOptions,
+        f: F,
+    ) -> Result<(), JSError>
+    where
+        F: Fn(CallContext, Vec<JSObject>) -> Fut + Sync + Send + 'static,
+        Fut: Future<Output = JSResult> + Send + 'static,
+    {
+        let func: BindingFunc =
+            Arc::new(move |info, args| Box::pin(f(CallContext::from(info), args)));
+        bind(&self.window, name, func, options.clone()).await
+    }
+
+    /// Set the origin policy of this window. Bindings are only callable from frames
+    /// whose origin both this policy and the binding's own policy allow.
+    ///
+    /// The policy is checked on every call, so changing it takes effect at once, also
+    /// for documents that are already loaded. Bindings stay visible to frames of
+    /// other origins, but their calls are rejected; use
+    /// [`BindingOptions::origin_policy()`] to hide a binding from them.
+    pub fn set_origin_policy(&self, policy: OriginPolicy) {
+        self.window.set_origin_policy(policy);
+    }
+
+    /// Returns a receiver of the binding calls that were rejected because of an origin
+    /// policy. The call is also rejected in JS with an error.
+    pub fn blocked_calls(&self) -> tokio::sync::broadcast::Receiver<BlockedCall> {
+        self.window.blocked_calls()
     }
 
     /// Evaluates js code and returns the result.
@@ -289,8 +1105,46 @@ impl UI {
         eval(&self.window, js).await
     }
 
+    /// Calls a JS function of the page, such as `app.store.dispatch`, and returns the
+    /// result. If the function returns a Promise it is awaited.
+    ///
+    /// The function is looked up by its dot separated path from `window` and called
+    /// with the object it belongs to as `this`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the function
+    /// * `args` - The arguments. They must serialize to a JSON array, for example a
+    ///   tuple like `(1, "two")` or a [`Vec`]. Use `()` for no arguments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![windows_subsystem = "windows"]
+    /// use alcro::{CallJSError, UIBuilder};
+    /// # tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    /// let ui = UIBuilder::new().custom_args(&["--headless"]).run().await.expect("Unable to launch");
+    /// ui.eval("window.app = { add: async (a, b) => a + b, fail() { throw new TypeError('bad'); } }")
+    ///     .await.unwrap();
+    /// assert_eq!(ui.call_js("app.add", (1, 2)).await.unwrap(), 3);
+    /// assert!(matches!(ui.call_js("app.fail", ()).await, Err(CallJSError::Exception { name, .. }) if name == "TypeError"));
+    /// assert!(matches!(ui.call_js("app.missing", ()).await, Err(CallJSError::NotAFunction(_))));
+    /// # });
+    /// ```
+    pub async fn call_js<A: serde::Serialize>(
+        &self,
+        path: &str,
+        args: A,
+    ) -> Result<JSObject, CallJSError> {
+        let args =
+            serde_json::to_value(args).map_err(|e| CallJSError::InvalidArguments(e.to_string()))?;
+        call_js(&self.window, path, args).await
+    }
+
     /// Evaluates js code and adds functions before document loads. Loaded js is unloaded on reload.
     ///
+    /// The script runs in every frame of the window, including iframes.
+    ///
     /// # Arguments
     ///
     /// * `script` - Javascript that should be loaded
@@ -312,6 +1166,8 @@ impl UI {
 
     /// Loads CSS into current window. Loaded CSS is unloaded on reload.
     ///
+    /// The CSS is added to every frame of the window, including iframes.
+    ///
     /// # Arguments
     ///
     /// * `css` - CSS that should be loaded
@@ -333,10 +1189,104 @@ impl UI {
     /// It changes the size, position or state of the browser window specified by the `Bounds` struct. It returns Err if it fails.
     ///
     /// To change the window state alone use `WindowState::to_bounds()`
+    ///
+    /// In headless mode there is no window: the page is rendered at the given width and
+    /// height instead, e.g. for screenshots at several viewport sizes. Only
+    /// [`WindowState::Normal`] is supported there.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![windows_subsystem = "windows"]
+    /// use alcro::{Bounds, UIBuilder, WindowState};
+    /// # tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    /// let ui = UIBuilder::new().custom_args(&["--headless"]).run().await.expect("Unable to launch");
+    /// let bounds = Bounds {
+    ///     left: 0,
+    ///     top: 0,
+    ///     width: 375,
+    ///     height: 667,
+    ///     window_state: WindowState::Normal,
+    /// };
+    /// ui.set_bounds(bounds).await.unwrap();
+    /// assert_eq!(ui.bounds().await.unwrap(), bounds);
+    /// assert_eq!(ui.eval("innerWidth").await.unwrap(), 375);
+    /// # });
+    /// ```
     pub async fn set_bounds(&self, b: Bounds) -> Result<(), JSError> {
         set_bounds(&self.window, b).await
     }
 
+    /// Emulate a device in this window: its viewport size, device scale factor (DPI),
+    /// mobile viewport handling, touch screen, user agent and zoom. It returns Err if
+    /// it fails.
+    ///
+    /// [`DeviceProfile`] has presets for common devices. The window itself keeps its
+    /// size; the page is laid out and rendered (e.g. in screenshots) as on the device.
+    /// The emulation lasts until [`UI::reset_emulation()`], across navigations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![windows_subsystem = "windows"]
+    /// use alcro::{DeviceProfile, UIBuilder};
+    /// # tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    /// let ui = UIBuilder::new().custom_args(&["--headless"]).run().await.expect("Unable to launch");
+    /// ui.emulate(DeviceProfile::iphone_se()).await.unwrap();
+    /// assert_eq!(ui.eval("innerWidth").await.unwrap(), 375);
+    /// assert_eq!(ui.eval("devicePixelRatio").await.unwrap(), 2);
+    ///
+    /// ui.emulate(DeviceProfile::desktop(1280, 800, 1.0).zoom(2.0)).await.unwrap();
+    /// assert_eq!(ui.eval("innerWidth").await.unwrap(), 640);
+    ///
+    /// ui.reset_emulation().await.unwrap();
+    /// # });
+    /// ```
+    pub async fn emulate(&self, profile: DeviceProfile) -> Result<(), JSError> {
+        emulate(&self.window, &profile).await
+    }
+
+    /// Stop emulating a device, restoring the window's own viewport (or the bounds a
+    /// headless window was given with [`UI::set_bounds()`]), scale factor, input and
+    /// user agent. It returns Err if it fails.
+    pub async fn reset_emulation(&self) -> Result<(), JSError> {
+        reset_emulation(&self.window).await
+    }
+
+    /// Returns the displays connected to the computer, with their work areas and scale
+    /// factors. It returns Err if it fails.
+    ///
+    /// The displays are listed with the browser's Window Management API. Its permission
+    /// is granted to the app's own pages, i.e. those served from an [`AssetProvider`] or
+    /// loaded from `file://` urls, which keep it until the browser exits. Other pages,
+    /// such as remote sites, are not granted it, so for them, as where the API is
+    /// unavailable, only the display the window is on is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![windows_subsystem = "windows"]
+    /// use alcro::UIBuilder;
+    /// # tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    /// let ui = UIBuilder::new().custom_args(&["--headless"]).run().await.expect("Unable to launch");
+    /// let displays = ui.displays().await.unwrap();
+    /// assert!(!displays.is_empty());
+    /// // Put saved bounds back on a visible display, e.g. after a monitor was unplugged
+    /// let saved = ui.bounds().await.unwrap();
+    /// ui.set_bounds(saved.clamp_to(&displays)).await.unwrap();
+    /// # });
+    /// ```
+    pub async fn displays(&self) -> Result<Vec<DisplayInfo>, JSError> {
+        displays(&self.window).await
+    }
+
+    /// Move the window to the middle of the work area of `display`, shrinking it if it
+    /// does not fit. It returns Err if it fails.
+    pub async fn center_on(&self, display: &DisplayInfo) -> Result<(), JSError> {
+        let current = bounds(&self.window).await.map_err(JSError::from)?;
+        set_bounds(&self.window, current.center_on(display)).await
+    }
+
     /// It gets the size, position and state of the browser window. It returns Err if it fails.
     pub async fn bounds(&self) -> Result<Bounds, JSObject> {
         bounds(&self.window).await
@@ -344,7 +1294,8 @@ impl UI {
 }
 
 /// Dropping a `UI` closes its window; when it is the last open window of the
-/// browser, the browser process is killed instead.
+/// browser, the browser process is killed instead. Handles borrowed from a
+/// [`CallContext`] are the exception and leave the window open.
 ///
 /// Drop cannot wait for a graceful shutdown; to close the browser gracefully call
 /// [`UI::close()`] and [`UI::wait_finish()`] before dropping.
@@ -356,10 +1307,12 @@ impl UI {
 /// dropped.
 impl Drop for UI {
     fn drop(&mut self) {
-        if self.window.is_closed() {
+        if !self.owned || self.window.is_closed() {
             return;
         }
-        if !self.window.has_other_live_windows() {
+        // A window opened by the page that was denied never starts
+        self.window.cancel_start();
+        if !self.window.has_other_live_windows() && !self.window.browser_kept_alive() {
             self.window.kill_browser();
         } else if let Ok(handle) = tokio::runtime::Handle::try_current() {
             let window = self.window.clone();
@@ -368,13 +1321,210 @@ impl Drop for UI {
     }
 }
 
+/// Where a binding registered with [`UI::bind_with_context()`] was called from.
+pub struct CallContext {
+    window: UI,
+    session: String,
+    context_id: i64,
+    origin: String,
+    url: String,
+}
+
+impl From<CallInfo> for CallContext {
+    fn from(info: CallInfo) -> Self {
+        CallContext {
+            window: UI {
+                window: info.window,
+                owned: false,
+            },
+            session: info.session,
+            context_id: info.context_id,
+            origin: info.origin,
+            url: info.url,
+        }
+    }
+}
+
+impl CallContext {
+    /// The window containing the calling frame. Dropping this handle does not close
+    /// the window.
+    pub fn window(&self) -> &UI {
+        &self.window
+    }
+
+    /// The url of the calling frame
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The origin of the calling frame, e.g. `https://example.com`
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    /// The devtools id of the calling JS execution context
+    pub fn execution_context_id(&self) -> i64 {
+        self.context_id
+    }
+
+    /// Evaluates js code in the calling frame and returns the result.
+    pub async fn eval(&self, js: &str) -> JSResult {
+        eval_in_context(&self.window.window, &self.session, self.context_id, js).await
+    }
+}
+
+fn policy_func<F, Fut>(f: F) -> PolicyFunc
+where
+    F: Fn(String) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = NavigationAction> + Send + 'static,
+{
+    Arc::new(move |url| -> PolicyFuture { Box::pin(f(url)) })
+}
+
+fn new_window_func<F, Fut>(f: F) -> NewWindowFunc
+where
+    F: Fn(UI) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    Arc::new(move |window| -> NewWindowFuture {
+        Box::pin(f(UI {
+            window,
+            owned: true,
+        }))
+    })
+}
+
 /// Specifies the type of content shown by the browser
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Clone)]
 pub enum Content<'a> {
     /// The URL
     Url(&'a str),
-    /// HTML text
+    /// HTML text. It is written into a blank document as is, so it can have any
+    /// size and contain any characters.
     Html(&'a str),
+    /// HTML text whose relative urls (e.g. in `<script src>` and `<link href>`)
+    /// resolve against a base url, as if it were the document at that url. The base
+    /// url is loaded first and its document is replaced by the HTML, so it must be
+    /// reachable: an http(s) url, one answered by a [handler](UIBuilder::handle_origin()),
+    /// or a local directory's url from [`dir_url()`].
+    HtmlWithBase {
+        /// The HTML text
+        html: &'a str,
+        /// The url relative urls resolve against
+        base_url: &'a str,
+    },
+    /// A local file. The path is made absolute and converted to a `file://` url,
+    /// so spaces and non-ASCII characters need no escaping.
+    File(std::path::PathBuf),
+    /// Files from an [`AssetProvider`], starting with `index.html`.
+    ///
+    /// The files are served to the browser directly, by intercepting its requests to
+    /// a fixed origin (`https://alcro.invalid`), without an HTTP server. Relative urls
+    /// in the files resolve against that origin as usual. Two `Assets` contents are
+    /// equal if they share the same provider.
+    Assets(Arc<dyn AssetProvider>),
+}
+
+impl Content<'_> {
+    fn source(&self) -> Source {
+        match self {
+            Content::Url(u) => Source::Url(u.to_string()),
+            Content::Html(h) => Source::Html {
+                html: h.to_string(),
+                base_url: "about:blank".to_string(),
+            },
+            Content::HtmlWithBase { html, base_url } => Source::Html {
+                html: html.to_string(),
+                base_url: base_url.to_string(),
+            },
+            Content::File(path) => Source::Url(file_url(path, false)),
+            Content::Assets(provider) => Source::Assets(Arc::clone(provider)),
+        }
+    }
+}
+
+/// The `file://` url of a local directory, made absolute and percent-encoded, e.g.
+/// as the base url of [`Content::HtmlWithBase`]. It ends with a `/`, so relative
+/// urls resolve inside the directory.
+pub fn dir_url(dir: &std::path::Path) -> String {
+    file_url(dir, true)
+}
+
+/// The `file://` url of `path`, made absolute and percent-encoded. Directory
+/// urls end with a `/` so relative urls resolve inside the directory.
+fn file_url(path: &std::path::Path, dir: bool) -> String {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut url = String::from("file://");
+    // Windows paths start with a drive letter instead of a `/`
+    if !path.as_os_str().as_encoded_bytes().starts_with(b"/") {
+        url.push('/');
+    }
+    for &b in path.as_os_str().as_encoded_bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                url.push(b as char)
+            }
+            b'\\' if cfg!(windows) => url.push('/'),
+            b':' if cfg!(windows) => url.push(':'),
+            _ => url.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    if dir && !url.ends_with('/') {
+        url.push('/');
+    }
+    url
+}
+
+impl std::fmt::Debug for Content<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Content::Url(u) => f.debug_tuple("Url").field(u).finish(),
+            Content::Html(h) => f.debug_tuple("Html").field(h).finish(),
+            Content::HtmlWithBase { html, base_url } => f
+                .debug_struct("HtmlWithBase")
+                .field("html", html)
+                .field("base_url", base_url)
+                .finish(),
+            Content::File(path) => f.debug_tuple("File").field(path).finish(),
+            Content::Assets(_) => f.debug_tuple("Assets").finish_non_exhaustive(),
+        }
+    }
+}
+
+impl PartialEq for Content<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Content::Url(a), Content::Url(b)) => a == b,
+            (Content::Html(a), Content::Html(b)) => a == b,
+            (
+                Content::HtmlWithBase { html, base_url },
+                Content::HtmlWithBase {
+                    html: other_html,
+                    base_url: other_url,
+                },
+            ) => html == other_html && base_url == other_url,
+            (Content::File(a), Content::File(b)) => a == b,
+            (Content::Assets(a), Content::Assets(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Content<'_> {}
+
+impl std::hash::Hash for Content<'_> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Content::Url(s) | Content::Html(s) => s.hash(state),
+            Content::HtmlWithBase { html, base_url } => {
+                html.hash(state);
+                base_url.hash(state);
+            }
+            Content::File(path) => path.hash(state),
+            Content::Assets(provider) => std::ptr::hash(Arc::as_ptr(provider) as *const (), state),
+        }
+    }
 }
 
 /// Builder for constructing a UI instance.
@@ -385,6 +1535,16 @@ pub struct UIBuilder<'a> {
     height: i32,
     custom_args: &'a [&'a str],
     log_output: Option<LogOutput>,
+    origin_policy: OriginPolicy,
+    handlers: Vec<(String, Arc<dyn RequestHandler>)>,
+    hot_reload_dir: Option<&'a std::path::Path>,
+    load_options: LoadOptions,
+    navigation_policy: Option<PolicyFunc>,
+    new_window_handler: Option<NewWindowFunc>,
+    remember_bounds: Option<&'a str>,
+    state: WindowState,
+    kiosk: bool,
+    show_after_load: bool,
 }
 
 impl<'a> Default for UIBuilder<'a> {
@@ -403,32 +1563,49 @@ impl<'a> UIBuilder<'a> {
             height: 600,
             custom_args: &[],
             log_output: None,
+            origin_policy: OriginPolicy::AllowAll,
+            handlers: vec![],
+            hot_reload_dir: None,
+            load_options: LoadOptions::default(),
+            navigation_policy: None,
+            new_window_handler: None,
+            remember_bounds: None,
+            state: WindowState::Normal,
+            kiosk: false,
+            show_after_load: false,
         }
     }
 
     /// Launch the browser, wait for the initial page to load and return the UI instance.
-    /// It returns the Err variant if any error occurs.
+    /// It returns the Err variant if any error occurs, including when the content fails
+    /// to load ([`UILaunchError::LoadError`]). To show a fallback page instead, launch
+    /// without content and [load](UI::load()) it afterwards.
     pub async fn run(&self) -> Result<UI, UILaunchError> {
-        let html: String;
-        let url = match self.content {
-            Content::Url(u) => u,
-            Content::Html(h) => {
-                html = format!("data:text/html,{}", h);
-                &html
-            }
-        };
-        UI::new(
-            url,
-            self.dir,
-            self.width,
-            self.height,
-            self.custom_args,
-            self.log_output.as_ref(),
-        )
-        .await
+        UI::new(self).await
+    }
+
+    /// Launch the browser without a window and return a [`Browser`] handle to open
+    /// windows with. The other settings, including [hot reloading](UIBuilder::hot_reload()),
+    /// apply to every window of the browser, except for those that only describe the
+    /// window [`UIBuilder::run()`] launches: the content,
+    /// [`remember_bounds`](UIBuilder::remember_bounds()), [`state`](UIBuilder::state()),
+    /// [`kiosk`](UIBuilder::kiosk()) and [`show_after_load`](UIBuilder::show_after_load())
+    /// are not used. Set the size, position and state of each window with
+    /// [`Browser::new_window_with_options()`]. It returns the Err variant if any error
+    /// occurs.
+    pub async fn run_browser(&self) -> Result<Browser, UILaunchError> {
+        let setup = LaunchSetup::new(self, true)?;
+        let args: Vec<&str> = setup.args.iter().map(String::as_str).collect();
+        let chrome =
+            launch_browser(&setup.chrome_path, &args, setup.log_sink, setup.tmpdir).await?;
+        Ok(Browser {
+            chrome,
+            config: setup.config,
+            hot_reload_dir: self.hot_reload_dir.map(std::path::Path::to_path_buf),
+        })
     }
 
-    /// Set the content (url or html text)
+    /// Set the content (url, local file, html text or assets)
     pub fn content(&mut self, content: Content<'a>) -> &mut Self {
         self.content = content;
         self
@@ -440,6 +1617,18 @@ impl<'a> UIBuilder<'a> {
         self
     }
 
+    /// Save the size, position and state of the window whenever they change, and
+    /// restore them the next time a window with the same `key` is launched. Restored
+    /// bounds are moved onto a visible display, e.g. if their display was unplugged.
+    ///
+    /// The bounds are saved to a file in the [user data
+    /// directory](UIBuilder::user_data_dir()), so set one to keep them across launches:
+    /// the default temporary directory is removed when the browser exits.
+    pub fn remember_bounds(&mut self, key: &'a str) -> &mut Self {
+        self.remember_bounds = Some(key);
+        self
+    }
+
     /// Set the window size
     pub fn size(&mut self, width: i32, height: i32) -> &mut Self {
         self.width = width;
@@ -447,6 +1636,41 @@ impl<'a> UIBuilder<'a> {
         self
     }
 
+    /// Set the state the window starts in. Maximized and fullscreen windows are
+    /// started so by the browser, and the state is set again once the content has
+    /// loaded; it is left alone if [remembered bounds](UIBuilder::remember_bounds())
+    /// are restored. It has no effect in headless mode.
+    pub fn state(&mut self, state: WindowState) -> &mut Self {
+        self.state = state;
+        self
+    }
+
+    /// Start the window in kiosk mode: fullscreen, without browser UI and without
+    /// the shortcuts that leave fullscreen or open other pages. Close it from rust,
+    /// e.g. with [`UI::close()`]. It has no effect in headless mode.
+    ///
+    /// This is the only way to a window without a frame: browsers cannot open app
+    /// windows without a title bar.
+    pub fn kiosk(&mut self, kiosk: bool) -> &mut Self {
+        self.kiosk = kiosk;
+        self
+    }
+
+    /// Keep the window out of sight until the initial content has loaded, so that
+    /// the blank page before it is never shown. The window starts outside of the
+    /// displays and is moved to its [remembered bounds](UIBuilder::remember_bounds()),
+    /// to the position passed with `--window-position` in the
+    /// [custom arguments](UIBuilder::custom_args()), or else to the middle of the
+    /// primary display, when [`UIBuilder::run()`] returns. Where the window manager
+    /// moves it back (e.g. on macOS) or does not let it choose its position (e.g. on
+    /// Wayland), it is minimized until then instead, and the empty window may show
+    /// for a moment before. A kiosk window is fullscreen from the start, so it is
+    /// shown right away. It has no effect in headless mode.
+    pub fn show_after_load(&mut self, show_after_load: bool) -> &mut Self {
+        self.show_after_load = show_after_load;
+        self
+    }
+
     /// Add custom arguments to spawn chrome with
     pub fn custom_args(&mut self, custom_args: &'a [&'a str]) -> &mut Self {
         self.custom_args = custom_args;
@@ -459,4 +1683,128 @@ impl<'a> UIBuilder<'a> {
         self.log_output = Some(log_output);
         self
     }
+
+    /// Set the origin policy of the window, see [`UI::set_origin_policy()`]. By default
+    /// all origins are allowed.
+    pub fn origin_policy(&mut self, policy: OriginPolicy) -> &mut Self {
+        self.origin_policy = policy;
+        self
+    }
+
+    /// Set how the initial content is loaded, see [`UI::load_with_options()`].
+    /// [`UIBuilder::run()`] returns once it is loaded.
+    pub fn load_options(&mut self, options: &LoadOptions) -> &mut Self {
+        self.load_options = options.clone();
+        self
+    }
+
+    /// Set the navigation policy of the window, see [`UI::set_navigation_policy()`].
+    /// It is in place before the content loads.
+    pub fn navigation_policy<F, Fut>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = NavigationAction> + Send + 'static,
+    {
+        self.navigation_policy = Some(policy_func(f));
+        self
+    }
+
+    /// Set the handler for windows the page opens, see [`UI::on_new_window()`].
+    pub fn on_new_window<F, Fut>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(UI) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.new_window_handler = Some(new_window_func(f));
+        self
+    }
+
+    /// Development mode: watch the frontend files in `dir` and update the window when
+    /// they change.
+    ///
+    /// If only CSS files the window loaded stylesheets from changed, the rules of
+    /// those stylesheets are replaced in place in every frame, through the devtools
+    /// CSS domain like [`UI::load_css()`]. Other changes, including new CSS files,
+    /// reload the window, which keeps bindings and
+    /// scripts added by [`UI::load_js()`], and CSS added by [`UI::load_css()`] is added
+    /// again. The directory is polled a few times per second, off the async runtime,
+    /// for as long as the window is open. Other windows of the browser are not updated.
+    pub fn hot_reload(&mut self, dir: &'a std::path::Path) -> &mut Self {
+        self.hot_reload_dir = Some(dir);
+        self
+    }
+
+    /// Answer requests to `origin` with a Rust handler, see [`UI::handle_origin()`].
+    /// The handler is in place before the content loads, so the content can be served
+    /// from the origin as well.
+    pub fn handle_origin(&mut self, origin: &str, handler: impl RequestHandler) -> &mut Self {
+        self.handlers.push((origin.to_string(), Arc::new(handler)));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    #[cfg(unix)]
+    fn test_file_url() {
+        use std::path::Path;
+        assert_eq!(
+            file_url(Path::new("/tmp/my app/ü#1.html"), false),
+            "file:///tmp/my%20app/%C3%BC%231.html"
+        );
+        assert_eq!(file_url(Path::new("/tmp/web"), true), "file:///tmp/web/");
+        assert_eq!(file_url(Path::new("/tmp/web/"), true), "file:///tmp/web/");
+        assert!(file_url(Path::new("index.html"), false).ends_with("/index.html"));
+    }
+
+    #[test]
+    fn test_window_args() {
+        let args = |b: &UIBuilder| window_args(b).join(" ");
+        assert_eq!(args(&UIBuilder::new()), "--app=about:blank");
+        assert_eq!(
+            args(UIBuilder::new().state(WindowState::Maximized)),
+            "--app=about:blank --start-maximized"
+        );
+        assert_eq!(
+            args(UIBuilder::new().state(WindowState::Fullscreen)),
+            "--app=about:blank --start-fullscreen"
+        );
+        // A hidden window gets its state once it is shown
+        assert_eq!(
+            args(
+                UIBuilder::new()
+                    .state(WindowState::Maximized)
+                    .show_after_load(true)
+            ),
+            format!("--app=about:blank {}", HIDDEN_WINDOW_POSITION)
+        );
+        // A kiosk window is shown right away
+        assert_eq!(
+            args(UIBuilder::new().kiosk(true).show_after_load(true)),
+            "--app=about:blank --kiosk"
+        );
+        assert!(window_args(UIBuilder::new().custom_args(&["--headless"]).kiosk(true)).is_empty());
+    }
+
+    #[test]
+    fn test_requested_position() {
+        assert_eq!(requested_position(&UIBuilder::new()), None);
+        assert_eq!(
+            requested_position(UIBuilder::new().custom_args(&["--window-position=10,-20"])),
+            Some((10, -20))
+        );
+        // The browser uses the last one
+        assert_eq!(
+            requested_position(
+                UIBuilder::new().custom_args(&["--window-position=1,2", "--window-position=3,4"])
+            ),
+            Some((3, 4))
+        );
+        assert_eq!(
+            requested_position(UIBuilder::new().custom_args(&["--window-position=x"])),
+            None
+        );
+    }
 }
@@ -1,4 +1,8 @@
-use alcro::{Content, UIBuilder};
+use alcro::{
+    Asset, AssetProvider, BindingOptions, Bounds, Content, DeviceProfile, FailReason,
+    InterceptAction, LoadError, LoadOptions, LogOutput, NavigationAction, NavigationEvent,
+    OriginPolicy, Request, Response, UIBuilder, WaitUntil, WindowEvent, WindowOptions, WindowState,
+};
 
 #[tokio::test(flavor = "multi_thread")]
 async fn test_content() {
@@ -100,3 +104,888 @@ async fn test_bind() {
 
     assert_eq!(ui.eval("foo('a')").await.unwrap(), "abcd");
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_bind_in_iframe() {
+    let ui = UIBuilder::new()
+        .content(Content::Html(
+            r#"<html><body><iframe srcdoc="<p>child</p>"></iframe></body></html>"#,
+        ))
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+
+    ui.bind("who", |_| async move { Ok("rust".into()) })
+        .await
+        .unwrap();
+    ui.load_js("var loaded = 'yes';").await.unwrap();
+
+    let frame = "document.querySelector('iframe').contentWindow";
+    assert_eq!(
+        ui.eval(&format!("(async () => await {}.who())()", frame))
+            .await
+            .unwrap(),
+        "rust"
+    );
+    assert_eq!(ui.eval(&format!("{}.loaded", frame)).await.unwrap(), "yes");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_origin_policy() {
+    let ui = UIBuilder::new()
+        .content(Content::Html("<html><body></body></html>"))
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+    let mut blocked = ui.blocked_calls();
+
+    ui.bind("open", |_| async move { Ok("open".into()) })
+        .await
+        .unwrap();
+    assert_eq!(ui.eval("(async () => await open())()").await.unwrap(), "open");
+
+    // Calls already exposed before the policy changed are rejected as well
+    ui.set_origin_policy(OriginPolicy::allow_list(&["https://app.local"]));
+    assert!(ui.eval("(async () => await open())()").await.is_err());
+    let call = blocked.recv().await.unwrap();
+    assert_eq!(call.name, "open");
+
+    // Bindings added under a policy work once it allows the origin again
+    ui.bind("late", |_| async move { Ok("late".into()) })
+        .await
+        .unwrap();
+    assert!(ui.eval("(async () => await late())()").await.is_err());
+    ui.set_origin_policy(OriginPolicy::AllowAll);
+    assert_eq!(ui.eval("(async () => await late())()").await.unwrap(), "late");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_binding_queue() {
+    let ui = UIBuilder::new()
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+
+    // Counts the calls that reached the binding function
+    let started = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = started.clone();
+    ui.bind_with_options(
+        "slow",
+        BindingOptions::new().serial().max_queue(1),
+        move |_, args| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            async move {
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                Ok(args[0].clone())
+            }
+        },
+    )
+    .await
+    .unwrap();
+
+    // The first call runs, the second waits and the third is rejected
+    let results = ui
+        .eval(
+            "Promise.allSettled([slow(1), slow(2), slow(3)]).then(r => r.map(x => x.status))",
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        results,
+        serde_json::json!(["fulfilled", "fulfilled", "rejected"])
+    );
+    assert_eq!(started.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+struct TestAssets;
+
+impl AssetProvider for TestAssets {
+    fn get(&self, path: &str) -> Option<Asset> {
+        let (data, mime_type): (&'static [u8], &str) = match path {
+            "index.html" => (b"<html><body><script src='js/main.js'></script></body></html>", "text/html"),
+            "js/main.js" => (b"document.body.innerText = 'from assets';", "text/javascript"),
+            _ => return None,
+        };
+        Some(Asset {
+            data: data.into(),
+            mime_type: mime_type.to_string(),
+        })
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_assets() {
+    let ui = UIBuilder::new()
+        .content(Content::Assets(std::sync::Arc::new(TestAssets)))
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+    assert_eq!(
+        ui.eval("document.body.innerText").await.unwrap(),
+        "from assets"
+    );
+    assert_eq!(
+        ui.eval("fetch('missing.txt').then(r => r.status)")
+            .await
+            .unwrap(),
+        404
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_handle_origin() {
+    let ui = UIBuilder::new()
+        .content(Content::Url("https://app.local/"))
+        .handle_origin("https://app.local", |req: Request| async move {
+            match (req.method.as_str(), req.url.as_str()) {
+                ("GET", "https://app.local/") => {
+                    Response::new(200, "<html><body>index</body></html>")
+                        .header("Content-Type", "text/html")
+                }
+                ("POST", "https://app.local/echo") => {
+                    Response::new(201, req.body.unwrap_or_default())
+                }
+                _ => Response::new(404, "not found"),
+            }
+        })
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+    assert_eq!(ui.eval("document.body.innerText").await.unwrap(), "index");
+    assert_eq!(
+        ui.eval("fetch('/echo', {method: 'POST', body: 'ping'}).then(async r => r.status + ' ' + await r.text())")
+            .await
+            .unwrap(),
+        "201 ping"
+    );
+    // Large bodies are not sent along with the paused request
+    assert_eq!(
+        ui.eval("fetch('/echo', {method: 'POST', body: 'x'.repeat(1 << 20)}).then(async r => (await r.text()).length)")
+            .await
+            .unwrap(),
+        1 << 20
+    );
+    // Inline HTML with the handled origin as its base
+    ui.load(Content::HtmlWithBase {
+        html: "<html><body><script>document.body.innerText = location.origin;</script></body></html>",
+        base_url: "https://app.local/",
+    })
+    .await
+    .unwrap();
+    assert_eq!(
+        ui.eval("document.body.innerText").await.unwrap(),
+        "https://app.local"
+    );
+    assert!(ui
+        .handle_origin("app://main", |_| async { Response::new(200, "") })
+        .await
+        .is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_file_content() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().join("my app ü");
+    std::fs::create_dir(&dir).unwrap();
+    std::fs::write(dir.join("main.js"), "document.body.innerText = 'from file';").unwrap();
+    std::fs::write(
+        dir.join("index #1.html"),
+        "<html><body><script src='main.js'></script></body></html>",
+    )
+    .unwrap();
+
+    let ui = UIBuilder::new()
+        .content(Content::File(dir.join("index #1.html")))
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+    assert_eq!(ui.eval("document.body.innerText").await.unwrap(), "from file");
+
+    ui.load(Content::HtmlWithBase {
+        html: "<html><body><script src='main.js'></script></body></html>",
+        base_url: &alcro::dir_url(&dir),
+    })
+    .await
+    .unwrap();
+    assert_eq!(ui.eval("document.body.innerText").await.unwrap(), "from file");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_html_verbatim() {
+    let text = "100% #1 %20 &amp;";
+    let ui = UIBuilder::new()
+        .content(Content::Html(&format!("<html><body>{}</body></html>", text)))
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+    assert_eq!(
+        ui.eval("document.body.innerText").await.unwrap(),
+        "100% #1 %20 &"
+    );
+
+    // Far beyond the length limit of urls
+    let big = "x".repeat(4 * 1024 * 1024);
+    ui.load(Content::Html(&format!("<html><body>{}</body></html>", big)))
+        .await
+        .unwrap();
+    assert_eq!(
+        ui.eval("document.body.innerText.length").await.unwrap(),
+        big.len()
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_intercept() {
+    let ui = UIBuilder::new()
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+    ui.intercept("https://example.com/*", |req: Request| async move {
+        match req.url.as_str() {
+            "https://example.com/" => InterceptAction::Fulfill(
+                Response::new(200, "<html><body>mocked</body></html>")
+                    .header("Content-Type", "text/html"),
+            ),
+            "https://example.com/old" => InterceptAction::Modify(Request {
+                url: "https://example.com/new".to_string(),
+                ..req
+            }),
+            "https://example.com/new" => InterceptAction::Fulfill(Response::new(200, "new")),
+            _ => InterceptAction::Fail(FailReason::BlockedByClient),
+        }
+    })
+    .await
+    .unwrap();
+
+    ui.load(Content::Url("https://example.com/")).await.unwrap();
+    assert_eq!(ui.eval("document.body.innerText").await.unwrap(), "mocked");
+    assert_eq!(
+        ui.eval("fetch('/old').then(r => r.text())").await.unwrap(),
+        "new"
+    );
+    assert!(ui.eval("fetch('/analytics')").await.is_err());
+
+    ui.remove_intercept("https://example.com/*").await.unwrap();
+    ui.load(Content::Url("https://example.com/")).await.unwrap();
+    assert_ne!(ui.eval("document.body.innerText").await.unwrap(), "mocked");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_hot_reload() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    std::fs::write(dir.join("style.css"), "body { color: rgb(255, 0, 0); }").unwrap();
+    std::fs::write(
+        dir.join("index.html"),
+        "<html><head><link rel='stylesheet' href='style.css'></head><body>v1</body></html>",
+    )
+    .unwrap();
+
+    let ui = UIBuilder::new()
+        .content(Content::File(dir.join("index.html")))
+        .hot_reload(dir)
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+    ui.bind("who", |_| async move { Ok("rust".into()) })
+        .await
+        .unwrap();
+    ui.load_css("body { margin: 7px; }").await.unwrap();
+    ui.eval("window.marker = 1").await.unwrap();
+    let other = ui
+        .new_window(Content::Html("<html><body>other</body></html>"))
+        .await
+        .unwrap();
+    other.eval("window.marker = 2").await.unwrap();
+
+    // A stylesheet change is applied without reloading the page
+    std::fs::write(dir.join("style.css"), "body { color: rgb(0, 0, 255); }").unwrap();
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    assert_eq!(
+        ui.eval("getComputedStyle(document.body).color").await.unwrap(),
+        "rgb(0, 0, 255)"
+    );
+    assert_eq!(ui.eval("window.marker").await.unwrap(), 1);
+
+    // Other changes reload the page, keeping the bindings and the loaded CSS
+    std::fs::write(
+        dir.join("index.html"),
+        "<html><head><link rel='stylesheet' href='style.css'></head><body>v2</body></html>",
+    )
+    .unwrap();
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    assert_eq!(ui.eval("document.body.innerText").await.unwrap(), "v2");
+    assert_eq!(ui.eval("typeof window.marker").await.unwrap(), "undefined");
+    assert_eq!(ui.eval("(async () => await who())()").await.unwrap(), "rust");
+    assert_eq!(ui.eval("getComputedStyle(document.body).margin").await.unwrap(), "7px");
+    // Only the watched window is reloaded
+    assert_eq!(other.eval("window.marker").await.unwrap(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_load_options() {
+    let ui = UIBuilder::new()
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+    let mut events = ui.navigation_events();
+
+    ui.load_with_options(
+        Content::Url("https://example.com/"),
+        LoadOptions::new().wait_until(WaitUntil::DomContentLoaded),
+    )
+    .await
+    .unwrap();
+    assert_eq!(events.recv().await.unwrap(), NavigationEvent::Started);
+    assert_eq!(
+        events.recv().await.unwrap(),
+        NavigationEvent::Committed {
+            url: "https://example.com/".to_string()
+        }
+    );
+    assert_eq!(
+        events.recv().await.unwrap(),
+        NavigationEvent::DomContentLoaded
+    );
+
+    let res = ui
+        .load_with_options(
+            Content::Html("<html><body></body></html>"),
+            LoadOptions::new()
+                .wait_until(WaitUntil::Predicate("window.never".to_string()))
+                .timeout(std::time::Duration::from_millis(500)),
+        )
+        .await;
+    assert!(matches!(res, Err(LoadError::Timeout(_))));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_navigation_error() {
+    let ui = UIBuilder::new()
+        .handle_origin("https://app.local", |_| async {
+            Response::new(404, "<html><body>missing</body></html>")
+        })
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+
+    match ui.load(Content::Url("https://app.local/missing")).await {
+        Err(LoadError::Navigation(e)) => {
+            assert_eq!(e.status, Some(404));
+            assert_eq!(e.net_error, None);
+        }
+        res => panic!("Unexpected result {:?}", res),
+    }
+    match ui.load(Content::Url("https://server.invalid/")).await {
+        Err(LoadError::Navigation(e)) => {
+            assert_eq!(e.net_error.as_deref(), Some("net::ERR_NAME_NOT_RESOLVED"));
+        }
+        res => panic!("Unexpected result {:?}", res),
+    }
+
+    let res = UIBuilder::new()
+        .content(Content::Url("https://server.invalid/"))
+        .custom_args(&["--headless"])
+        .run()
+        .await;
+    assert!(matches!(
+        res,
+        Err(alcro::UILaunchError::LoadError(LoadError::Navigation(_)))
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_navigation_policy() {
+    let blocked_served = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let served = blocked_served.clone();
+    let ui = UIBuilder::new()
+        .content(Content::Url("https://app.local/"))
+        .handle_origin("https://app.local", move |req: Request| {
+            if req.url.ends_with("/blocked") {
+                served.store(true, std::sync::atomic::Ordering::SeqCst);
+            }
+            async move {
+                if req.url.ends_with("/slow") {
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                }
+                Response::new(200, format!("<html><body>{}</body></html>", req.url))
+                    .header("Content-Type", "text/html")
+            }
+        })
+        .navigation_policy(|url| async move {
+            if url.ends_with("/blocked") {
+                NavigationAction::Cancel
+            } else {
+                NavigationAction::Allow
+            }
+        })
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+    let mut events = ui.navigation_events();
+
+    ui.eval("location.href = '/blocked'").await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(
+        ui.eval("location.href").await.unwrap(),
+        "https://app.local/"
+    );
+
+    ui.eval("location.href = '/next'").await.unwrap();
+    while events.recv().await.unwrap() != NavigationEvent::Loaded {}
+    assert_eq!(
+        ui.eval("document.body.innerText").await.unwrap(),
+        "https://app.local/next"
+    );
+
+    // A navigation by the page while a load from rust is in flight still goes by
+    // the policy
+    let _ = tokio::join!(ui.load(Content::Url("https://app.local/slow")), async {
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        let _ = ui.eval("location.href = '/blocked'").await;
+    });
+    assert!(!blocked_served.load(std::sync::atomic::Ordering::SeqCst));
+
+    // Loads from rust are not subject to the policy
+    ui.load(Content::Url("https://app.local/blocked"))
+        .await
+        .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_history() {
+    let ui = UIBuilder::new()
+        .content(Content::Url("https://app.local/first"))
+        .handle_origin("https://app.local", |req: Request| async move {
+            let page = req.url.rsplit('/').next().unwrap_or("").to_string();
+            Response::new(
+                200,
+                format!("<html><head><title>{0}</title></head><body>{0}</body></html>", page),
+            )
+            .header("Content-Type", "text/html")
+        })
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+    ui.load(Content::Url("https://app.local/second"))
+        .await
+        .unwrap();
+    assert_eq!(ui.title().await.unwrap(), "second");
+
+    assert!(ui.go_back().await.unwrap());
+    assert_eq!(
+        ui.current_url().await.unwrap(),
+        "https://app.local/first"
+    );
+    assert_eq!(ui.eval("document.body.innerText").await.unwrap(), "first");
+
+    assert!(ui.go_forward().await.unwrap());
+    assert_eq!(ui.eval("document.body.innerText").await.unwrap(), "second");
+    assert!(!ui.go_forward().await.unwrap());
+
+    ui.eval("window.marker = 1").await.unwrap();
+    ui.reload(false).await.unwrap();
+    assert_eq!(ui.eval("typeof window.marker").await.unwrap(), "undefined");
+    assert_eq!(ui.eval("document.body.innerText").await.unwrap(), "second");
+
+    let history = ui.navigation_history().await.unwrap();
+    assert_eq!(history.current_index, history.entries.len() - 1);
+    assert_eq!(history.entries[history.current_index].title, "second");
+    ui.stop_loading().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_window_events() {
+    let ui = UIBuilder::new()
+        .content(Content::Html("<html><body>events</body></html>"))
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+    let mut events = ui.window_events();
+    // The observer's binding is out of reach of the page
+    assert_eq!(
+        ui.eval("typeof alcroWindowEvent").await.unwrap(),
+        "undefined"
+    );
+    // Feed bad payloads to a raw binding caught before its wrapper is installed
+    ui.eval(
+        "window.grab = setInterval(() => { \
+           const f = window.probe; \
+           if (f && !f.callbacks) { f('not json'); f('{\"seq\": \"x\"}'); f('[]'); } \
+         }, 0)",
+    )
+    .await
+    .unwrap();
+    ui.bind("probe", |_| async move { Ok("alive".into()) })
+        .await
+        .unwrap();
+    ui.eval("clearInterval(grab)").await.unwrap();
+    // Spoofed events are ignored, the window keeps working
+    ui.eval("window.dispatchEvent(new FocusEvent('focus'))")
+        .await
+        .unwrap();
+    assert_eq!(ui.eval("probe()").await.unwrap(), "alive");
+    let bounds = Bounds {
+        left: 0,
+        top: 0,
+        width: 400,
+        height: 300,
+        window_state: WindowState::Normal,
+    };
+    ui.set_bounds(bounds).await.unwrap();
+    let event = tokio::time::timeout(std::time::Duration::from_secs(5), events.recv())
+        .await
+        .expect("No window event")
+        .unwrap();
+    assert_eq!(event, WindowEvent::BoundsChanged(bounds));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_close_requested() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let ui = UIBuilder::new()
+        .content(Content::Html("<html><body>unsaved</body></html>"))
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+    let asked = Arc::new(AtomicUsize::new(0));
+    let asked_handler = asked.clone();
+    ui.on_close_requested(move || {
+        let asked = asked_handler.clone();
+        async move {
+            asked.fetch_add(1, Ordering::SeqCst);
+            false
+        }
+    })
+    .await
+    .unwrap();
+
+    // Leaving the page from JS asks the handler, which cancels it
+    ui.eval("window.marker = 1; setTimeout(() => location.reload())")
+        .await
+        .unwrap();
+    for _ in 0..50 {
+        if asked.load(Ordering::SeqCst) > 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(asked.load(Ordering::SeqCst), 1);
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(ui.eval("window.marker").await.unwrap(), 1);
+
+    // Loads from rust are not asked
+    ui.load(Content::Html("<html><body>loaded</body></html>"))
+        .await
+        .unwrap();
+    assert_eq!(asked.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_browser() {
+    let browser = UIBuilder::new()
+        .custom_args(&["--headless"])
+        .run_browser()
+        .await
+        .expect("Unable to launch");
+    assert!(browser.windows().is_empty());
+
+    let ui = browser
+        .new_window(Content::Html("<html><body>first</body></html>"))
+        .await
+        .unwrap();
+    let ui2 = browser
+        .new_window(Content::Html("<html><body>second</body></html>"))
+        .await
+        .unwrap();
+    assert_eq!(browser.windows().len(), 2);
+    let mut results: Vec<String> = browser
+        .broadcast("document.body.innerText")
+        .await
+        .into_iter()
+        .map(|r| r.unwrap().as_str().unwrap().to_string())
+        .collect();
+    results.sort();
+    assert_eq!(results, ["first", "second"]);
+
+    // The browser keeps running without windows
+    ui.close().await;
+    ui.wait_finish().await;
+    drop(ui2);
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert!(browser.windows().is_empty());
+    assert!(!browser.done());
+    let ui = browser
+        .new_window(Content::Html("<html><body>again</body></html>"))
+        .await
+        .unwrap();
+    assert_eq!(ui.eval("document.body.innerText").await.unwrap(), "again");
+
+    browser.close().await;
+    browser.wait_finish().await;
+    assert!(browser.done());
+    assert!(ui.done());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_new_window() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let served = Arc::new(AtomicUsize::new(0));
+    let served_handler = served.clone();
+    let ui = UIBuilder::new()
+        .handle_origin("https://app.local", move |req: Request| {
+            if req.url.ends_with("/denied") {
+                served_handler.fetch_add(1, Ordering::SeqCst);
+            }
+            async move {
+                Response::new(
+                    200,
+                    "<html><body><script>window.early = typeof add + ' ' + typeof fromOpener;</script></body></html>",
+                )
+                .header("Content-Type", "text/html")
+            }
+        })
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+    ui.bind("add", |args| async move {
+        let sum: i64 = args.iter().map(|n| n.as_i64().unwrap_or(0)).sum();
+        Ok(sum.into())
+    })
+    .await
+    .unwrap();
+    ui.load_js("window.fromOpener = 1;").await.unwrap();
+    let (send, mut recv) = tokio::sync::mpsc::unbounded_channel();
+    let opened = Arc::new(AtomicUsize::new(0));
+    ui.on_new_window(move |popup| {
+        let send = send.clone();
+        let first = opened.fetch_add(1, Ordering::SeqCst) == 0;
+        async move {
+            // Keep the first window, deny the others by dropping them
+            if first {
+                let _ = send.send(popup);
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    // A kept window has the opener's bindings and scripts before its page runs
+    ui.eval("window.kept = window.open('https://app.local/kept')")
+        .await
+        .unwrap();
+    let popup = tokio::time::timeout(std::time::Duration::from_secs(10), recv.recv())
+        .await
+        .expect("No new window")
+        .unwrap();
+    let mut early = serde_json::Value::Null;
+    for _ in 0..50 {
+        early = popup.eval("window.early || null").await.unwrap();
+        if !early.is_null() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(early, "function number");
+    assert_eq!(popup.eval("add(1, 2)").await.unwrap(), 3);
+    assert_eq!(ui.eval("window.kept.closed").await.unwrap(), false);
+
+    // A denied window is closed before it loads anything
+    ui.eval("window.denied = window.open('https://app.local/denied')")
+        .await
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+    assert_eq!(ui.eval("window.denied.closed").await.unwrap(), true);
+    assert_eq!(served.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_window_options() {
+    let ui = UIBuilder::new()
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+    let dir = tempfile::TempDir::new().unwrap();
+    let log = dir.path().join("window.log");
+    let ui2 = ui
+        .new_window_with_options(
+            Content::Html("<html><body>second</body></html>"),
+            WindowOptions::new()
+                .size(400, 300)
+                .position(10, 20)
+                .log_output(LogOutput::File(log.clone())),
+        )
+        .await
+        .unwrap();
+    assert_eq!(ui2.eval("document.body.innerText").await.unwrap(), "second");
+
+    // Only the new window logs to its own file
+    ui.eval("console.log('from first')").await.unwrap();
+    ui2.eval("console.log('from second')").await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    let logged = std::fs::read_to_string(&log).unwrap();
+    assert!(logged.contains("from second"));
+    assert!(!logged.contains("from first"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_headless_bounds() {
+    let ui = UIBuilder::new()
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+    for (width, height) in [(320, 480), (1024, 768)] {
+        let bounds = Bounds {
+            left: 0,
+            top: 0,
+            width,
+            height,
+            window_state: WindowState::Normal,
+        };
+        ui.set_bounds(bounds).await.unwrap();
+        assert_eq!(ui.bounds().await.unwrap(), bounds);
+        assert_eq!(ui.eval("innerWidth").await.unwrap(), width);
+        assert_eq!(ui.eval("innerHeight").await.unwrap(), height);
+    }
+    assert!(ui
+        .set_bounds(WindowState::Maximized.to_bounds())
+        .await
+        .is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_emulate() {
+    let ui = UIBuilder::new()
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+    let bounds = Bounds {
+        left: 0,
+        top: 0,
+        width: 640,
+        height: 480,
+        window_state: WindowState::Normal,
+    };
+    ui.set_bounds(bounds).await.unwrap();
+    ui.emulate(DeviceProfile::iphone_se()).await.unwrap();
+    assert_eq!(ui.eval("innerWidth").await.unwrap(), 375);
+    assert_eq!(ui.eval("devicePixelRatio").await.unwrap(), 2);
+    assert_eq!(
+        ui.eval("navigator.userAgent.includes('iPhone')")
+            .await
+            .unwrap(),
+        true
+    );
+    assert_eq!(ui.eval("navigator.maxTouchPoints > 0").await.unwrap(), true);
+
+    ui.emulate(DeviceProfile::desktop(1000, 800, 1.0).zoom(2.0))
+        .await
+        .unwrap();
+    assert_eq!(ui.eval("innerWidth").await.unwrap(), 500);
+    assert_eq!(ui.eval("devicePixelRatio").await.unwrap(), 2);
+
+    ui.reset_emulation().await.unwrap();
+    assert_eq!(
+        ui.eval("navigator.userAgent.includes('iPhone')")
+            .await
+            .unwrap(),
+        false
+    );
+    assert_eq!(ui.eval("navigator.maxTouchPoints").await.unwrap(), 0);
+    // The headless window is back to the bounds it was given
+    assert_eq!(ui.bounds().await.unwrap(), bounds);
+    assert_eq!(ui.eval("innerWidth").await.unwrap(), 640);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_displays() {
+    let ui = UIBuilder::new()
+        .custom_args(&["--headless"])
+        .run()
+        .await
+        .expect("Unable to launch");
+    let displays = ui.displays().await.unwrap();
+    assert!(!displays.is_empty());
+    assert!(displays.iter().all(|d| d.scale_factor > 0.0));
+    let display = &displays[0];
+    assert!(display.work_area.width <= display.bounds.width);
+
+    ui.center_on(display).await.unwrap();
+    let bounds = ui.bounds().await.unwrap();
+    assert_eq!(bounds, bounds.clamp_to(&displays));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_remember_bounds() {
+    let tmp = tempfile::tempdir().unwrap();
+    let bounds = Bounds {
+        left: 40,
+        top: 30,
+        width: 640,
+        height: 480,
+        window_state: WindowState::Normal,
+    };
+
+    let ui = UIBuilder::new()
+        .custom_args(&["--headless"])
+        .user_data_dir(tmp.path())
+        .remember_bounds("main")
+        .run()
+        .await
+        .expect("Unable to launch");
+    ui.set_bounds(bounds).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    ui.close().await;
+    ui.wait_finish().await;
+    assert!(tmp.path().join("alcro-bounds-main.json").exists());
+
+    let ui = UIBuilder::new()
+        .custom_args(&["--headless"])
+        .user_data_dir(tmp.path())
+        .remember_bounds("main")
+        .run()
+        .await
+        .expect("Unable to launch");
+    let restored = ui.bounds().await.unwrap();
+    assert_eq!((restored.width, restored.height), (640, 480));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_launch_modes() {
+    // The launch modes have no effect in headless mode, but must not stop the
+    // window from launching and loading its content
+    let ui = UIBuilder::new()
+        .content(Content::Html("<html><body>loaded</body></html>"))
+        .custom_args(&["--headless"])
+        .state(WindowState::Maximized)
+        .kiosk(true)
+        .show_after_load(true)
+        .run()
+        .await
+        .expect("Unable to launch");
+    assert_eq!(ui.eval("document.body.innerText").await.unwrap(), "loaded");
+    assert_eq!(ui.bounds().await.unwrap().window_state, WindowState::Normal);
+}